@@ -0,0 +1,406 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::OsString,
+    io::{self, Read, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::anyhow;
+use getset::{CopyGetters, Getters};
+use path_absolutize::Absolutize;
+use serde::{Deserialize, Serialize};
+use size::Size;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{
+    error_behavior::ErrorBehavior,
+    fs::{
+        self,
+        tree::snapshot::TreeSnapshot,
+        vfs::{DirEntry, FileSystem, MemoryFileSystem, PathMetadata, RealFileSystem},
+    },
+    parallel,
+};
+
+/// bumped whenever the catalog format changes
+const CATALOG_VERSION: u32 = 1;
+
+const CATALOG_EXTENSION: &str = "lsccat";
+
+/// returns `$XDG_DATA_HOME/lsclones/catalogs` or `~/.local/share/lsclones/catalogs`
+pub fn default_catalogs_dir() -> anyhow::Result<PathBuf> {
+    let data_dir = match env::var_os("XDG_DATA_HOME") {
+        Some(data_dir) if !data_dir.is_empty() => PathBuf::from(data_dir),
+        _ => env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".local/share"))
+            .ok_or_else(|| anyhow!("cannot determine the catalogs directory: HOME is not set"))?,
+    };
+    Ok(data_dir.join("lsclones").join("catalogs"))
+}
+
+/// hashes the content of a file
+pub fn hash_file(path: impl AsRef<Path>) -> io::Result<u128> {
+    let mut file = fs_err::File::open(path.as_ref())?;
+    let mut hasher = Xxh3::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.digest128())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CatalogFile {
+    path: OsString,
+    size: u64,
+    hash: u128,
+}
+
+/// file listing, sizes and hashes of a drive recorded so that it can be queried while the drive is not attached
+#[derive(Debug, Serialize, Deserialize, Getters, CopyGetters)]
+pub struct Catalog {
+    version: u32,
+    #[getset(get = "pub")]
+    name: String,
+    #[getset(get = "pub")]
+    mountpoint: OsString,
+    #[getset(get_copy = "pub")]
+    created: SystemTime,
+    tree: TreeSnapshot,
+    files: Vec<CatalogFile>,
+}
+
+impl Catalog {
+    /// hashes all the files under `mountpoint` found in `file_tree`, which must have been built by walking the absolute path of `mountpoint`
+    /// calls progress with the number of files hashed so far and the number of files to hash
+    pub fn create(
+        mountpoint: impl AsRef<Path>,
+        name: impl Into<String>,
+        file_tree: &fs::Tree,
+        error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
+        progress: impl FnMut(usize, usize),
+    ) -> anyhow::Result<Self> {
+        let name = name.into();
+        if name.is_empty() || name.contains('/') {
+            return Err(anyhow!("invalid catalog name: `{name}`"));
+        }
+        let mountpoint = mountpoint.as_ref().absolutize()?.to_path_buf();
+        if !mountpoint.is_dir() {
+            return Err(anyhow!("not a directory: {}", mountpoint.to_string_lossy()));
+        }
+
+        let files = file_tree
+            .traverse_path_file_nodes(&mountpoint, fs::tree::TraversalOrder::Pre)?
+            .map(|file| (file.path().to_path_buf(), file.size().unwrap_or_default()))
+            .collect::<Vec<_>>();
+
+        let hashes = hash_files(&files, threads, progress);
+        let mut catalog_files = Vec::with_capacity(files.len());
+        for ((path, size), hash) in files.into_iter().zip(hashes) {
+            if let Some(hash) = error_behavior.handle(hash, &path, "failed to hash file")? {
+                catalog_files.push(CatalogFile {
+                    path: path.into_os_string(),
+                    size,
+                    hash,
+                });
+            }
+        }
+
+        let mut tree = TreeSnapshot::default();
+        tree.update(file_tree, &[mountpoint.as_path()]);
+
+        Ok(Self {
+            version: CATALOG_VERSION,
+            name,
+            mountpoint: mountpoint.into_os_string(),
+            created: SystemTime::now(),
+            tree,
+            files: catalog_files,
+        })
+    }
+
+    fn path(catalogs_dir: impl AsRef<Path>, name: &str) -> PathBuf {
+        catalogs_dir
+            .as_ref()
+            .join(format!("{name}.{CATALOG_EXTENSION}"))
+    }
+
+    /// reads the catalog named `name` from `catalogs_dir`
+    pub fn load(catalogs_dir: impl AsRef<Path>, name: &str) -> anyhow::Result<Self> {
+        let path = Self::path(&catalogs_dir, name);
+        if !path.is_file() {
+            return Err(anyhow!(
+                "no catalog named `{name}` in {}",
+                catalogs_dir.as_ref().to_string_lossy()
+            ));
+        }
+        Self::load_file(path)
+    }
+
+    fn load_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = fs_err::File::open(path)?;
+        let catalog: Self =
+            bincode::deserialize_from(io::BufReader::new(file)).map_err(|error| {
+                anyhow!("failed reading catalog {}: {error}", path.to_string_lossy())
+            })?;
+        if catalog.version != CATALOG_VERSION {
+            return Err(anyhow!(
+                "unsupported catalog version {} in {}",
+                catalog.version,
+                path.to_string_lossy()
+            ));
+        }
+        Ok(catalog)
+    }
+
+    /// writes the catalog to `catalogs_dir`, replacing the catalog with the same name
+    pub fn save(&self, catalogs_dir: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
+        fs_err::create_dir_all(catalogs_dir.as_ref())?;
+        let path = Self::path(catalogs_dir, &self.name);
+        let mut tmp_path = path.as_os_str().to_os_string();
+        tmp_path.push(".tmp");
+        let mut file = io::BufWriter::new(fs_err::File::create(&tmp_path)?);
+        bincode::serialize_into(&mut file, self).map_err(|error| {
+            anyhow!("failed writing catalog {}: {error}", path.to_string_lossy())
+        })?;
+        file.flush()?;
+        drop(file);
+        fs_err::rename(&tmp_path, &path)?;
+        Ok(path)
+    }
+
+    /// reads all the catalogs found in `catalogs_dir` sorted by name
+    pub fn list(catalogs_dir: impl AsRef<Path>) -> anyhow::Result<Vec<Self>> {
+        let catalogs_dir = catalogs_dir.as_ref();
+        if !catalogs_dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut catalogs = vec![];
+        for entry in fs_err::read_dir(catalogs_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == CATALOG_EXTENSION) {
+                catalogs.push(Self::load_file(path)?);
+            }
+        }
+        catalogs.sort_unstable_by(|c1, c2| c1.name.cmp(&c2.name));
+        Ok(catalogs)
+    }
+
+    /// file system holding the recorded listing of the drive, to analyse it like the real file system
+    pub fn file_system(&self) -> MemoryFileSystem {
+        MemoryFileSystem::from_snapshot(&self.tree)
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn total_size(&self) -> Size {
+        Size::from_bytes(self.files.iter().map(|file| file.size).sum::<u64>())
+    }
+
+    /// looks for files with the same content as `files` in the catalog, `files` being (path, size) pairs of files on a live file system
+    /// returns the path in the catalog of a file with the same content for each file
+    /// only the files having the size of a cataloged file are hashed, progress is called with the number of files hashed so far and the number of files to hash
+    pub fn locate_files(
+        &self,
+        files: &[(PathBuf, u64)],
+        threads: NonZeroUsize,
+        progress: impl FnMut(usize, usize),
+    ) -> Vec<io::Result<Option<&Path>>> {
+        let sizes = self
+            .files
+            .iter()
+            .map(|file| file.size)
+            .collect::<HashSet<_>>();
+        let content_index = self
+            .files
+            .iter()
+            .map(|file| ((file.size, file.hash), Path::new(&file.path)))
+            .collect::<HashMap<_, _>>();
+        let candidates = files
+            .iter()
+            .filter(|(_, size)| sizes.contains(size))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut hashes = hash_files(&candidates, threads, progress).into_iter();
+        files
+            .iter()
+            .map(|(_, size)| {
+                if !sizes.contains(size) {
+                    return Ok(None);
+                }
+                let hash = hashes.next().unwrap()?;
+                Ok(content_index.get(&(*size, hash)).copied())
+            })
+            .collect()
+    }
+}
+
+/// file system in which the paths under the mountpoints of the catalogs whose drive is not attached are listed from
+/// the catalogs, a drive being considered not attached when its mountpoint is missing or empty, the other paths are
+/// listed from the real file system
+#[derive(Debug, Default)]
+pub struct CatalogsFileSystem {
+    offline: Vec<(PathBuf, MemoryFileSystem)>,
+}
+
+impl CatalogsFileSystem {
+    pub fn new<'a>(catalogs: impl IntoIterator<Item = &'a Catalog>) -> Self {
+        let offline = catalogs
+            .into_iter()
+            .filter(|catalog| {
+                fs_err::read_dir(catalog.mountpoint())
+                    .map_or(true, |mut entries| entries.next().is_none())
+            })
+            .map(|catalog| (PathBuf::from(catalog.mountpoint()), catalog.file_system()))
+            .collect();
+        Self { offline }
+    }
+
+    /// returns the mountpoints listed from the catalogs
+    pub fn offline_mountpoints(&self) -> impl Iterator<Item = &Path> {
+        self.offline
+            .iter()
+            .map(|(mountpoint, _)| mountpoint.as_path())
+    }
+
+    /// returns the file system `path` is listed from along with the path to query it with, which is absolute for
+    /// the catalogs as they record absolute paths
+    fn resolve(&self, path: &Path) -> io::Result<(&dyn FileSystem, PathBuf)> {
+        let abs_path = path.absolutize()?;
+        Ok(
+            match self
+                .offline
+                .iter()
+                .find(|(mountpoint, _)| abs_path.starts_with(mountpoint))
+            {
+                Some((_, fs)) => (fs, abs_path.to_path_buf()),
+                None => (&RealFileSystem, path.to_path_buf()),
+            },
+        )
+    }
+}
+
+impl FileSystem for CatalogsFileSystem {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<DirEntry>>> {
+        let (fs, dir) = self.resolve(dir)?;
+        fs.read_dir(&dir)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<PathMetadata> {
+        let (fs, path) = self.resolve(path)?;
+        fs.metadata(&path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let (fs, path) = self.resolve(path)?;
+        fs.read_link(&path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let (fs, path) = self.resolve(path)?;
+        fs.canonicalize(&path)
+    }
+}
+
+fn hash_files(
+    files: &[(PathBuf, u64)],
+    threads: NonZeroUsize,
+    mut progress: impl FnMut(usize, usize),
+) -> Vec<io::Result<u128>> {
+    let file_count = files.len();
+    let hashes = parallel::map_with_progress(
+        files,
+        threads,
+        |(path, _)| hash_file(path),
+        |processed| progress(processed, file_count),
+    );
+    progress(file_count, file_count);
+    hashes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clones::db::ClonesDB,
+        fs::walk_bounds::WalkBounds,
+        path::HashedAbsolutePath,
+        paths::{Clones, PathRefs},
+    };
+
+    #[test]
+    fn offline_catalog_file_system() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mountpoint = temp_dir.path().join("drive");
+        fs_err::create_dir_all(mountpoint.join("dir")).unwrap();
+        std::fs::write(mountpoint.join("dir/file"), "file").unwrap();
+        let mut file_tree = fs::Tree::default();
+        file_tree
+            .extend(&mountpoint, ErrorBehavior::Stop, NonZeroUsize::MIN)
+            .unwrap();
+        let catalog = Catalog::create(
+            &mountpoint,
+            "drive",
+            &file_tree,
+            ErrorBehavior::Stop,
+            NonZeroUsize::MIN,
+            |_, _| {},
+        )
+        .unwrap();
+
+        // the drive is attached
+        let catalogs_fs = CatalogsFileSystem::new([&catalog]);
+        assert_eq!(catalogs_fs.offline_mountpoints().count(), 0);
+
+        // the drive is detached, leaving its mountpoint empty
+        fs_err::remove_dir_all(mountpoint.join("dir")).unwrap();
+        let catalogs_fs = CatalogsFileSystem::new([&catalog]);
+        assert_eq!(
+            catalogs_fs.offline_mountpoints().collect::<Vec<_>>(),
+            vec![mountpoint.as_path()]
+        );
+        assert_eq!(
+            catalogs_fs
+                .metadata(&mountpoint.join("dir/file"))
+                .unwrap()
+                .metadata()
+                .size(),
+            4
+        );
+        assert!(catalogs_fs.is_dir(temp_dir.path()));
+        assert!(!catalogs_fs.exists(&temp_dir.path().join("other")));
+
+        // the files of the detached drive are queried from its catalog
+        let file = mountpoint.join("dir/file");
+        let clones_db = ClonesDB::from_clone_groups([(
+            4,
+            vec![
+                HashedAbsolutePath::from(file.as_path()),
+                HashedAbsolutePath::from(temp_dir.path().join("other/file")),
+            ],
+        )]);
+        let paths = PathRefs::new(vec![file.as_path()]);
+        let (_, clones) = paths.clones(false, &WalkBounds::default(), &clones_db, &catalogs_fs);
+        assert_eq!(clones.into_iter().collect::<Vec<_>>(), vec![file.as_path()]);
+        let dirs = PathRefs::new(vec![mountpoint.as_path()]);
+        let clone_groups = dirs.clone_groups(
+            true,
+            false,
+            &WalkBounds::default(),
+            &clones_db,
+            &catalogs_fs,
+        );
+        assert_eq!(clone_groups.len(), 1);
+    }
+}
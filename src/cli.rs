@@ -1,10 +1,26 @@
-use std::path::PathBuf;
+use std::{num::NonZeroUsize, path::PathBuf, time::SystemTime};
 
+use anyhow::anyhow;
 use clap::{Args, Parser, Subcommand};
 use getset::{CopyGetters, Getters};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 
-use crate::{error_behavior::ErrorBehavior, log_level::LogLevel, paths::PathRefs};
+use lsclones::{
+    catalog::{self, Catalog, CatalogsFileSystem},
+    config,
+    error_behavior::ErrorBehavior,
+    fs::{
+        attribute_filter::{AttributeFilter, FileType},
+        symlinks::SymlinkPolicy,
+        tree::snapshot::SnapshotOptions,
+        walk_bounds::WalkBounds,
+    },
+    path::{filter::PathFilter, ignore::IgnoreFiles},
+    paths::PathRefs,
+};
+
+use crate::log_level::LogLevel;
 
 #[derive(Parser, Getters, CopyGetters)]
 #[clap(author, version, about, long_about = None)]
@@ -12,13 +28,24 @@ pub struct Cli {
     /// clones list file in JSON format produced by the `fclones` utility
     #[clap(short, long, env = "CLONES_LIST")]
     #[getset(get = "pub")]
-    clones_list: PathBuf,
+    clones_list: Option<PathBuf>,
 
-    /// prune non-existing files and directories from the clones list
+    /// prune non-existing files and directories, and files whose size changed, from the clones list
     #[clap(short, long)]
     #[getset(get_copy = "pub")]
     prune: bool,
 
+    /// configuration file in JSON format, defaults to `$XDG_CONFIG_HOME/lsclones/config.json`
+    #[clap(long, env = "LSC_CONFIG", value_name = "FILE")]
+    #[getset(get = "pub")]
+    config: Option<PathBuf>,
+
+    /// glob pattern of a location whose files never count as copies, such as `/tmp` or `**/.Trash*`, can be repeated,
+    /// added to the `not_a_backup` patterns of the configuration file
+    #[clap(long, value_name = "GLOB")]
+    #[getset(get = "pub")]
+    not_a_backup: Vec<String>,
+
     #[clap(short, long, value_enum, default_value_t = LogLevel::Info)]
     #[getset(get_copy = "pub")]
     log_level: LogLevel,
@@ -40,6 +67,229 @@ pub struct CommonOptions {
     /// display stats about what is listed
     #[clap(short = 'S', long, global = true)]
     stats: bool,
+
+    /// number of threads used to list directories, defaults to the number of available CPUs
+    #[clap(short = 'j', long, global = true)]
+    #[getset(skip)]
+    threads: Option<NonZeroUsize>,
+
+    /// save the listed directories to this file and reuse them in the next runs instead of listing the directories again
+    #[clap(long, global = true, value_name = "FILE")]
+    #[getset(skip)]
+    tree_snapshot: Option<PathBuf>,
+
+    /// list again the directories which have been modified since the tree snapshot was saved
+    #[clap(long, global = true, requires = "tree_snapshot")]
+    revalidate: bool,
+
+    /// only consider the files matching this glob pattern, or inside a directory matching it, can be repeated
+    #[clap(long, global = true, value_name = "GLOB")]
+    #[getset(skip)]
+    include: Vec<String>,
+
+    /// leave out the files and directories matching this glob pattern, can be repeated
+    #[clap(long, global = true, value_name = "GLOB")]
+    #[getset(skip)]
+    exclude: Vec<String>,
+
+    /// only consider the files whose path matches this regular expression, can be repeated
+    #[clap(long, global = true, value_name = "REGEX")]
+    #[getset(skip)]
+    regex: Vec<String>,
+
+    /// do not read the `.lscignore` files and the global ignore file
+    #[clap(long, global = true)]
+    no_ignore: bool,
+
+    /// only consider the files of at least this size, such as `100M`, `1.5GiB` or `500kB`
+    #[clap(long, global = true, value_name = "SIZE", value_parser = size_parser)]
+    #[getset(skip)]
+    min_size: Option<u64>,
+
+    /// only consider the files of at most this size
+    #[clap(long, global = true, value_name = "SIZE", value_parser = size_parser)]
+    #[getset(skip)]
+    max_size: Option<u64>,
+
+    /// only consider the files modified after this time, a duration ago such as `30days` or a UTC date such as `2024-01-31`,
+    /// only checked for the files in the listed directories
+    #[clap(long, global = true, value_name = "TIME", value_parser = time_parser)]
+    #[getset(skip)]
+    newer_than: Option<SystemTime>,
+
+    /// only consider the files modified before this time
+    #[clap(long, global = true, value_name = "TIME", value_parser = time_parser)]
+    #[getset(skip)]
+    older_than: Option<SystemTime>,
+
+    /// only consider the files of this type, recognized by their extension, can be repeated
+    #[clap(long = "type", global = true, value_enum, value_name = "TYPE")]
+    #[getset(skip)]
+    file_types: Vec<FileType>,
+
+    /// only consider the files at least this number of levels below the specified directories,
+    /// the files directly in them being at level 1, the shallower clones count as outside clones
+    #[clap(long, global = true, value_name = "LEVELS")]
+    #[getset(skip)]
+    min_depth: Option<usize>,
+
+    /// do not descend more than this number of levels below the specified directories, the files directly in them
+    /// being at level 1, the deeper clones count as outside clones
+    #[clap(long, global = true, value_name = "LEVELS")]
+    #[getset(skip)]
+    max_depth: Option<usize>,
+
+    /// do not descend into the directories on other file systems than the specified directories, such as mounted drives,
+    /// their clones count as outside clones
+    #[clap(short = 'x', long, global = true)]
+    #[getset(skip)]
+    one_file_system: bool,
+
+    /// what to do with the symbolic links, `report` lists the dangling ones and the ones pointing into the clone dirs
+    /// with the `dirs` command
+    #[clap(long, global = true, value_enum, default_value_t = SymlinkPolicy::Ignore)]
+    symlinks: SymlinkPolicy,
+
+    /// list the paths under the mountpoint of this catalog from the catalog when its drive is not attached, that is
+    /// when the mountpoint is missing or empty, can be repeated
+    #[clap(long = "catalog", global = true, value_name = "NAME")]
+    #[getset(skip)]
+    catalogs: Vec<String>,
+
+    /// directory where the catalogs are stored, defaults to `$XDG_DATA_HOME/lsclones/catalogs`
+    #[clap(long, env = "LSC_CATALOGS_DIR", global = true)]
+    #[getset(skip)]
+    catalogs_dir: Option<PathBuf>,
+}
+
+/// parses a size in bytes with an optional unit, `k`, `m`, `g` and `t` with or without `iB` being powers of 1024
+/// and `kB`, `MB`, `GB` and `TB` powers of 1000
+fn size_parser(size_str: &str) -> Result<u64, String> {
+    let size_str = size_str.trim();
+    let unit_start = size_str
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size_str.len());
+    let (value, unit) = size_str.split_at(unit_start);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid size value: `{value}`"))?;
+    let unit = unit.trim();
+    let exponent = match unit.chars().next().map(|c| c.to_ascii_lowercase()) {
+        None | Some('b') => 0,
+        Some('k') => 1,
+        Some('m') => 2,
+        Some('g') => 3,
+        Some('t') => 4,
+        Some(_) => return Err(format!("invalid size unit: `{unit}`")),
+    };
+    let base: f64 = match unit.get(1..).unwrap_or_default() {
+        "" | "i" | "iB" | "ib" => 1024.0,
+        "B" | "b" if exponent > 0 => 1000.0,
+        _ => return Err(format!("invalid size unit: `{unit}`")),
+    };
+    Ok((value * base.powi(exponent)) as u64)
+}
+
+/// parses a duration ago, such as `30days` or `2h`, or a UTC date with an optional time, such as `2024-01-31` or `2024-01-31 12:00:00`
+fn time_parser(time_str: &str) -> Result<SystemTime, String> {
+    if let Ok(duration) = humantime::parse_duration(time_str) {
+        return SystemTime::now()
+            .checked_sub(duration)
+            .ok_or_else(|| format!("duration too long: `{time_str}`"));
+    }
+    let date_time = if time_str.contains([' ', 'T']) {
+        time_str.to_owned()
+    } else {
+        format!("{time_str} 00:00:00")
+    };
+    humantime::parse_rfc3339_weak(&date_time).map_err(|_| {
+        "expected a duration such as `30days` or a date such as `2024-01-31`".to_owned()
+    })
+}
+
+impl CommonOptions {
+    pub fn threads(&self) -> NonZeroUsize {
+        self.threads
+            .unwrap_or_else(|| std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+    }
+
+    pub fn catalogs_dir(&self) -> anyhow::Result<PathBuf> {
+        match &self.catalogs_dir {
+            Some(catalogs_dir) => Ok(catalogs_dir.clone()),
+            None => catalog::default_catalogs_dir(),
+        }
+    }
+
+    /// file system listing the mountpoints of the catalogs given with `--catalog` from them while their drives are
+    /// not attached
+    pub fn file_system(&self) -> anyhow::Result<CatalogsFileSystem> {
+        let catalogs_dir = self.catalogs_dir()?;
+        let catalogs = self
+            .catalogs
+            .iter()
+            .map(|name| Catalog::load(&catalogs_dir, name))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(CatalogsFileSystem::new(&catalogs))
+    }
+
+    pub fn tree_snapshot(&self) -> Option<SnapshotOptions> {
+        Some(SnapshotOptions::new(
+            self.tree_snapshot.clone()?,
+            self.revalidate,
+        ))
+    }
+
+    /// the `.lscignore` files are honoured unless disabled with `--no-ignore`
+    pub fn path_filter(&self) -> anyhow::Result<PathFilter> {
+        let path_filter = PathFilter::new(&self.include, &self.exclude, &self.regex)?
+            .with_attributes(AttributeFilter::new(
+                self.min_size,
+                self.max_size,
+                self.newer_than,
+                self.older_than,
+                self.file_types.clone(),
+            ))
+            .with_bounds(WalkBounds::new(
+                self.min_depth,
+                self.max_depth,
+                self.one_file_system,
+            ))
+            .with_follow_symlinks(self.symlinks == SymlinkPolicy::Follow);
+        if self.no_ignore {
+            return Ok(path_filter);
+        }
+        let global_ignore_file = config::default_ignore_path().ok();
+        Ok(path_filter.with_ignore_files(IgnoreFiles::new(global_ignore_file.as_deref())?))
+    }
+
+    /// returns an error if some of the options leaving files out of the analysis are set, for the commands which
+    /// need all the files of the directory to give a safe answer
+    pub fn check_no_file_selection(&self, command: &str) -> anyhow::Result<()> {
+        let options = [
+            ("--include", !self.include.is_empty()),
+            ("--exclude", !self.exclude.is_empty()),
+            ("--regex", !self.regex.is_empty()),
+            ("--min-size", self.min_size.is_some()),
+            ("--max-size", self.max_size.is_some()),
+            ("--newer-than", self.newer_than.is_some()),
+            ("--older-than", self.older_than.is_some()),
+            ("--type", !self.file_types.is_empty()),
+            ("--min-depth", self.min_depth.is_some()),
+            ("--max-depth", self.max_depth.is_some()),
+            ("--one-file-system", self.one_file_system),
+        ];
+        let set_options = options
+            .iter()
+            .filter_map(|&(option, set)| set.then_some(option))
+            .collect_vec();
+        if set_options.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "{} cannot be used with the {command} command",
+            set_options.join(", ")
+        ))
+    }
 }
 
 lazy_static! {
@@ -81,9 +331,10 @@ pub struct DirsCommandPaths {
     dirs: Vec<PathBuf>,
 }
 
+/// the missing directories are accepted as they can be listed from a catalog, the walks fail on them otherwise
 fn dir_parser(path_str: &str) -> Result<PathBuf, &'static str> {
     let path = PathBuf::from(path_str);
-    if !path.is_dir() {
+    if path.exists() && !path.is_dir() {
         return Err("not a directory");
     }
     Ok(path)
@@ -119,6 +370,31 @@ pub enum Commands {
         #[clap(short, long)]
         unique: bool,
 
+        /// display groups of identical directories, containing the same clones under the same names, wherever they are in the specified directories
+        #[clap(short, long, group = "dir_comparison", conflicts_with_all = ["unique", "map", "null_line_terminator"])]
+        identical: bool,
+
+        /// ignore the file and directory names when comparing directories with --identical or --subsets
+        #[clap(long, requires = "dir_comparison")]
+        ignore_names: bool,
+
+        /// display the directories whose files all have a clone in a single other directory containing more, along with that directory
+        #[clap(long, group = "dir_comparison", conflicts_with_all = ["unique", "map", "identical", "null_line_terminator"])]
+        subsets: bool,
+
+        /// only count the clones inside these reference directories, such as an archive, when looking for clone dirs
+        #[clap(long, num_args = 1.., value_name = "DIR", conflicts_with_all = ["unique", "identical", "subsets"])]
+        against: Vec<PathBuf>,
+
+        /// consider all the specified directories as a single one: the clones in any of them do not count as outside clones
+        #[clap(long, conflicts_with_all = ["unique", "identical", "subsets"])]
+        single_scope: bool,
+
+        /// only hide the clone dirs without any file matching the --include, --exclude and --regex filters from the output,
+        /// instead of leaving the filtered files out of the clone dirs computation
+        #[clap(long, conflicts_with_all = ["unique", "identical", "subsets"])]
+        hide_filtered: bool,
+
         /// use \0 line terminator to print paths so that the output can be piped to `xargs -0`
         #[clap(short = '0', global = true, conflicts_with = "map")]
         null_line_terminator: bool,
@@ -156,6 +432,14 @@ pub enum Commands {
         #[clap(short, long, conflicts_with = "unique")]
         outside: bool,
 
+        /// only count the outside clones inside these reference directories, such as an archive
+        #[clap(long, num_args = 1.., value_name = "DIR", requires = "map")]
+        against: Vec<PathBuf>,
+
+        /// consider all the specified paths as a single one: the clones in any of them are inside clones
+        #[clap(long, conflicts_with = "unique")]
+        single_scope: bool,
+
         /// use \0 line terminator to print paths so that the output can be piped to `xargs -0`
         #[clap(short = '0', global = true, conflicts_with = "map")]
         null_line_terminator: bool,
@@ -163,4 +447,217 @@ pub enum Commands {
         #[clap(flatten)]
         paths: FilesCommandPaths,
     },
+
+    /// rank directories by how much of their content they share
+    ///
+    /// with a directory: list the directories containing the most of its content
+    /// without: list the pairs of directories sharing the most content
+    Similar {
+        #[clap(flatten)]
+        global_options: CommonOptions,
+
+        /// number of matches to display
+        #[clap(short, long, default_value_t = 10)]
+        top: usize,
+
+        /// display which files are missing or extra in the matching directories
+        #[clap(short = 'd', long)]
+        details: bool,
+
+        /// directories to look for similar directories in, defaults to the current directory
+        #[clap(short, long = "in", value_name = "DIR", value_parser = dir_parser)]
+        within: Vec<PathBuf>,
+
+        /// specify what to do in case there is an error while listing a directory
+        #[clap(short = 'E', long, value_enum, default_value_t = ErrorBehavior::Stop)]
+        error_behavior: ErrorBehavior,
+
+        /// directory to find similar directories of
+        #[clap(value_parser = dir_parser)]
+        dir: Option<PathBuf>,
+    },
+
+    /// list the files of a directory which have no clone under any of the reference directories
+    ///
+    /// the files outside of the paths scanned to build the clones list are also listed as their clones are unknown,
+    /// the exit status is 0 only if all the files are covered
+    Covered {
+        #[clap(flatten)]
+        global_options: CommonOptions,
+
+        /// reference directories, such as backups, the files need to have a clone in
+        #[clap(long, required = true, num_args = 1.., value_name = "DIR")]
+        by: Vec<PathBuf>,
+
+        /// use \0 line terminator to print paths so that the output can be piped to `xargs -0`
+        #[clap(short = '0')]
+        null_line_terminator: bool,
+
+        /// specify what to do in case there is an error while listing a directory, only `stop` is accepted since the files
+        /// of the directories which could not be listed would not be checked
+        #[clap(short = 'E', long, value_enum, default_value_t = ErrorBehavior::Stop)]
+        error_behavior: ErrorBehavior,
+
+        #[clap(value_parser = dir_parser)]
+        dir: PathBuf,
+    },
+
+    /// list the unique files and clone groups which do not have enough copies
+    ///
+    /// followed by the size of the contents at risk per top-level directory, the directories directly under the specified paths
+    Replication {
+        #[clap(flatten)]
+        global_options: CommonOptions,
+
+        /// minimum number of copies a content needs to have
+        #[clap(long, default_value_t = 2, value_name = "N")]
+        min_copies: usize,
+
+        /// count the copies on the same device as one copy
+        #[clap(long)]
+        distinct_devices: bool,
+
+        /// specify what to do in case there is an error while listing a directory
+        #[clap(short = 'E', long, value_enum, default_value_t = ErrorBehavior::Stop)]
+        error_behavior: ErrorBehavior,
+
+        #[clap(flatten)]
+        paths: FilesCommandPaths,
+    },
+
+    /// plan the merge of a directory into another one so that it becomes a clone dir of it
+    ///
+    /// lists the files of `source` which already have a copy in `target` and can be dropped, the files to copy to
+    /// their relative path in `target` and the files whose relative path is taken by a different file in `target`
+    MergePlan {
+        #[clap(flatten)]
+        global_options: CommonOptions,
+
+        /// output the plan as a shell script copying the files, the conflicts being written as comments
+        #[clap(long)]
+        script: bool,
+
+        /// specify what to do in case there is an error while listing a directory
+        #[clap(short = 'E', long, value_enum, default_value_t = ErrorBehavior::Stop)]
+        error_behavior: ErrorBehavior,
+
+        #[clap(value_parser = dir_parser)]
+        source: PathBuf,
+
+        #[clap(value_parser = dir_parser)]
+        target: PathBuf,
+    },
+
+    /// copy the files preventing a directory from being a clone dir to a target directory
+    ///
+    /// each copy is checked, then the directory is checked again to tell whether it can be deleted
+    Consolidate {
+        #[clap(flatten)]
+        global_options: CommonOptions,
+
+        /// directory to copy the files to, under the same relative path
+        #[clap(long, value_name = "TARGET")]
+        into: PathBuf,
+
+        /// move the files instead of copying them
+        #[clap(long = "move")]
+        move_files: bool,
+
+        /// specify what to do in case there is an error while listing a directory or transferring a file, the directory
+        /// is always checked again after the transfers stopping on the listing errors
+        #[clap(short = 'E', long, value_enum, default_value_t = ErrorBehavior::Stop)]
+        error_behavior: ErrorBehavior,
+
+        #[clap(value_parser = dir_parser)]
+        dir: PathBuf,
+    },
+
+    /// manage catalogs of drives which can be queried while the drives are not attached
+    Catalog {
+        #[clap(flatten)]
+        global_options: CommonOptions,
+
+        #[command(subcommand)]
+        command: CatalogCommands,
+    },
+}
+
+impl Commands {
+    /// returns the paths the command lists or queries, the files of the clones list under them being filtered
+    pub fn queried_paths(&self) -> PathRefs {
+        match self {
+            Commands::Dirs { dirs, .. } => CommandArgsPaths::paths(dirs),
+            Commands::Files { paths, .. } | Commands::Replication { paths, .. } => {
+                CommandArgsPaths::paths(paths)
+            }
+            Commands::Similar { within, dir, .. } => {
+                let mut paths = if within.is_empty() {
+                    vec![DOT_PATHBUF.as_path()]
+                } else {
+                    within.iter().map(PathBuf::as_path).collect_vec()
+                };
+                paths.extend(dir.as_deref());
+                PathRefs::new(paths)
+            }
+            Commands::Covered { dir, .. } | Commands::Consolidate { dir, .. } => {
+                PathRefs::new(vec![dir.as_path()])
+            }
+            Commands::MergePlan { source, target, .. } => {
+                PathRefs::new(vec![source.as_path(), target.as_path()])
+            }
+            Commands::Catalog { .. } => PathRefs::default(),
+        }
+    }
+
+    pub fn global_options(&self) -> &CommonOptions {
+        match self {
+            Commands::Dirs { global_options, .. }
+            | Commands::Files { global_options, .. }
+            | Commands::Similar { global_options, .. }
+            | Commands::Covered { global_options, .. }
+            | Commands::Replication { global_options, .. }
+            | Commands::MergePlan { global_options, .. }
+            | Commands::Consolidate { global_options, .. }
+            | Commands::Catalog { global_options, .. } => global_options,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum CatalogCommands {
+    /// record the files listing, sizes and hashes of the drive mounted on `mountpoint` in a catalog named `name`
+    Create {
+        #[clap(value_parser = dir_parser)]
+        mountpoint: PathBuf,
+
+        name: String,
+
+        /// specify what to do in case there is an error while listing a directory or hashing a file
+        #[clap(short = 'E', long, value_enum, default_value_t = ErrorBehavior::Stop)]
+        error_behavior: ErrorBehavior,
+    },
+
+    /// list the catalogs
+    List,
+
+    /// list the files which are not on the cataloged drive
+    Check {
+        /// catalog name
+        name: String,
+
+        /// list the files which are on the cataloged drive instead, along with their path on the drive
+        #[clap(short, long)]
+        present: bool,
+
+        /// use \0 line terminator to print paths so that the output can be piped to `xargs -0`
+        #[clap(short = '0', conflicts_with = "present")]
+        null_line_terminator: bool,
+
+        /// specify what to do in case there is an error while listing a directory or hashing a file
+        #[clap(short = 'E', long, value_enum, default_value_t = ErrorBehavior::Stop)]
+        error_behavior: ErrorBehavior,
+
+        #[clap(flatten)]
+        paths: FilesCommandPaths,
+    },
 }
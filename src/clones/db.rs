@@ -1,29 +1,32 @@
 use std::{
-    collections::{hash_map, BTreeMap, HashMap},
-    fmt::Debug,
-    io::{self, Write},
-    path::{self, Path},
+    collections::{BTreeSet, HashMap, HashSet},
+    num::NonZeroUsize,
+    path::Path,
 };
 
-use crossterm::cursor;
 use derive_more::{Add, AddAssign, Constructor, Deref, IntoIterator};
 use getset::{CopyGetters, Getters};
 use itertools::Itertools;
-use num_format::{Locale, ToFormattedString};
-use ouroboros::self_referencing;
 use path_absolutize::Absolutize;
-use scopeguard::defer;
 use size::Size;
 
 use crate::{
-    call_rate_limiter::CallRateLimiter,
-    path::{
-        HashedAbsolutePath, HashedAbsolutePathRef, HashedAbsolutePathRefSet, HashedAbsolutePathSet,
-    },
+    fs::{vfs::FileSystem, walk_bounds::WalkBounds},
+    parallel,
+    path::{HashedAbsolutePath, HashedAbsolutePathSet, PathId},
 };
 
 use super::File;
 
+/// index of a clone group in the clone groups arena
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GroupId(u32);
+
+/// index of a file in the clone files arena
+/// file IDs are allocated in path order so sorting them sorts the files by path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
 pub trait CloneGroupFileCountAndSize {
     fn file_size(&self) -> u64;
     fn total_count(&self) -> usize;
@@ -36,12 +39,30 @@ pub trait CloneGroupReclaimable {
 }
 
 #[derive(Debug, Deref, CopyGetters, Getters)]
+pub struct CloneFile {
+    #[getset(get_copy = "pub")]
+    id: FileId,
+    #[getset(get_copy = "pub")]
+    group_id: GroupId,
+    #[deref]
+    #[getset(get = "pub")]
+    path: HashedAbsolutePath,
+    /// in a location which is never trusted as a surviving copy, such as `/tmp`
+    #[getset(get_copy = "pub")]
+    not_a_backup: bool,
+}
+
+/// clone files with the same content, either a whole group of the clones list or a part of it
+/// files are sorted by ID and thus by path
+#[derive(Debug, Clone, Deref, CopyGetters, Getters)]
 pub struct CloneGroup {
+    #[getset(get_copy = "pub")]
+    id: GroupId,
     #[getset(get_copy = "pub")]
     file_size: u64,
     #[deref]
     #[getset(get = "pub")]
-    files: HashedAbsolutePathSet,
+    files: Vec<FileId>,
 }
 
 impl CloneGroupFileCountAndSize for CloneGroup {
@@ -59,67 +80,77 @@ impl CloneGroupFileCountAndSize for CloneGroup {
 }
 
 impl CloneGroup {
-    pub fn from_parts(file_size: u64, files: HashedAbsolutePathSet) -> anyhow::Result<Self> {
-        Ok(Self { file_size, files })
-    }
-}
-
-#[derive(Debug, Deref, CopyGetters, Getters, Clone)]
-pub struct CloneRefGroup<'a> {
-    #[getset(get_copy = "pub")]
-    file_size: u64,
-    #[deref]
-    #[getset(get = "pub")]
-    files: HashedAbsolutePathRefSet<'a>,
-}
-
-impl CloneGroupFileCountAndSize for CloneRefGroup<'_> {
-    fn file_size(&self) -> u64 {
-        self.file_size
+    pub fn from_parts(id: GroupId, file_size: u64, files: Vec<FileId>) -> Self {
+        Self {
+            id,
+            file_size,
+            files,
+        }
     }
 
-    fn total_count(&self) -> usize {
-        self.files.len()
+    pub fn contains_file(&self, file_id: FileId) -> bool {
+        self.files.binary_search(&file_id).is_ok()
     }
 
-    fn total_size(&self) -> u64 {
-        self.files.len() as u64 * self.file_size
+    pub fn paths<'a>(
+        &'a self,
+        clone_groups: &'a CloneGroups,
+    ) -> impl Iterator<Item = &'a HashedAbsolutePath> + 'a {
+        self.files
+            .iter()
+            .map(|&file_id| clone_groups.file_path(file_id))
     }
-}
 
-impl<'a> CloneRefGroup<'a> {
-    pub fn from_parts(file_size: u64, files: HashedAbsolutePathRefSet<'a>) -> Self {
-        Self { file_size, files }
+    /// returns the part of the group for which `predicate` returns true
+    pub fn filter(
+        &self,
+        clone_groups: &CloneGroups,
+        mut predicate: impl FnMut(&CloneFile) -> bool,
+    ) -> CloneGroup {
+        let files = self
+            .files
+            .iter()
+            .copied()
+            .filter(|&file_id| predicate(clone_groups.file(file_id)))
+            .collect();
+        CloneGroup::from_parts(self.id, self.file_size, files)
     }
 
-    pub fn total_count(&self) -> usize {
-        self.files.len()
+    pub fn filter_out_dir(&self, dir: PathId, clone_groups: &CloneGroups) -> CloneGroup {
+        self.filter(clone_groups, |file| !file.starts_with_id(dir))
     }
 
-    pub fn total_size(&self) -> u64 {
-        self.total_count() as u64 * self.file_size
+    /// returns the part of the group which is outside of all of `dirs`
+    pub fn filter_out_dirs(
+        &self,
+        dirs: &[HashedAbsolutePath],
+        clone_groups: &CloneGroups,
+    ) -> CloneGroup {
+        self.filter(clone_groups, |file| {
+            !dirs.iter().any(|dir| file.starts_with_id(dir.id()))
+        })
     }
 
-    pub fn reclaimable_count(&self) -> usize {
-        if self.is_empty() {
-            return 0;
-        }
-        self.files.len() - 1
+    /// returns the part of the group which is inside one of `dirs`
+    pub fn filter_in_dirs(
+        &self,
+        dirs: &[HashedAbsolutePath],
+        clone_groups: &CloneGroups,
+    ) -> CloneGroup {
+        self.filter(clone_groups, |file| {
+            dirs.iter().any(|dir| file.starts_with_id(dir.id()))
+        })
     }
 
-    pub fn reclaimable_size(&self) -> u64 {
-        self.reclaimable_count() as u64 * self.file_size
+    /// returns the part of the group which can be trusted as copies, the not-a-backup files left out
+    pub fn backups(&self, clone_groups: &CloneGroups) -> CloneGroup {
+        self.filter(clone_groups, |file| !file.not_a_backup())
     }
 
-    pub fn filter_out_dir(&self, dir: impl AsRef<Path>) -> CloneRefGroup {
-        let dir = HashedAbsolutePath::from(dir.as_ref());
-        let files = self
-            .files
-            .iter()
-            .filter(|ifile| !ifile.starts_with_hashed_path(&dir))
-            .cloned()
-            .collect();
-        CloneRefGroup::from_parts(self.file_size, files)
+    /// returns true if one of the files of the group is inside `dir`
+    pub fn has_file_in_dir(&self, dir: PathId, clone_groups: &CloneGroups) -> bool {
+        self.paths(clone_groups)
+            .any(|file| file.starts_with_id(dir))
     }
 }
 
@@ -137,80 +168,49 @@ impl<T: CloneGroupFileCountAndSize> CloneGroupReclaimable for T {
 }
 
 #[derive(Debug, CopyGetters, Deref, Constructor)]
-pub struct FileClones<'a> {
+pub struct FileClones {
     #[getset(get_copy = "pub")]
     file_size: u64,
     #[deref]
-    clones: HashedAbsolutePathRefSet<'a>,
+    clones: Vec<FileId>,
 }
 
-impl FileClones<'_> {
+impl FileClones {
     pub fn reclaimable_size(&self) -> u64 {
         self.clones.len() as u64 * self.file_size
     }
 }
 
-#[self_referencing]
+/// clone groups and files stored in arenas and addressed through `GroupId` and `FileId`
+#[derive(Debug, Default)]
 pub struct CloneGroups {
     groups: Vec<CloneGroup>,
-    #[borrows(groups)]
-    #[covariant]
-    ref_groups: Vec<CloneRefGroup<'this>>,
-    #[borrows(ref_groups)]
-    #[covariant]
-    files: BTreeMap<&'this path::Path, &'this CloneRefGroup<'this>>,
-}
-
-impl Debug for CloneGroups {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("CloneGroups")
-            .field("groups", self.borrow_groups())
-            .finish()
-    }
+    files: Vec<CloneFile>,
+    path_files: HashMap<PathId, FileId>,
 }
 
-pub struct CloneFilesIter<'a> {
-    groups_iter: std::slice::Iter<'a, CloneRefGroup<'a>>,
-    group_iter: Option<std::collections::btree_set::Iter<'a, HashedAbsolutePathRef<'a>>>,
-}
-
-impl<'a> Iterator for CloneFilesIter<'a> {
-    type Item = &'a HashedAbsolutePathRef<'a>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if let Some(group_iter) = &mut self.group_iter {
-                if let Some(file) = group_iter.next() {
-                    return Some(file);
-                }
-            }
-
-            if let Some(group) = self.groups_iter.next() {
-                self.group_iter = Some(group.iter());
-            } else {
-                return None;
-            }
-        }
-    }
-}
+pub type CloneFilesIter<'a> = std::slice::Iter<'a, CloneFile>;
 
 pub struct DirCloneFilesIter<'a> {
     files_iter: CloneFilesIter<'a>,
-    dir_hash: Option<u64>,
+    dir_id: PathId,
     recursive: bool,
 }
 
 impl<'a> Iterator for DirCloneFilesIter<'a> {
-    type Item = &'a HashedAbsolutePathRef<'a>;
+    type Item = &'a CloneFile;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.files_iter.find(|file| {
-            if self.recursive {
-                file.comp_hash_matches(self.dir_hash.unwrap())
-            } else {
-                file.parent_hash() == self.dir_hash
+        loop {
+            let file = self.files_iter.next()?;
+            // files are sorted by path so the files inside the dir are contiguous
+            if !file.starts_with_id(self.dir_id) {
+                return None;
             }
-        })
+            if self.recursive || file.parent_id() == Some(self.dir_id) {
+                return Some(file);
+            }
+        }
     }
 }
 
@@ -221,7 +221,7 @@ pub enum PathClonesIter<'a> {
 }
 
 impl<'a> Iterator for PathClonesIter<'a> {
-    type Item = &'a HashedAbsolutePathRef<'a>;
+    type Item = &'a CloneFile;
 
     fn next(&mut self) -> Option<Self::Item> {
         use PathClonesIter::*;
@@ -239,19 +239,18 @@ pub struct PathsClonesIter<'a> {
     path_index: usize,
     path_clones_iter: Option<PathClonesIter<'a>>,
     recursive: bool,
-    returned_hashes: HashMap<u64, ()>,
+    returned: HashSet<FileId>,
+    fs: &'a dyn FileSystem,
 }
 
 impl<'a> Iterator for PathsClonesIter<'a> {
-    type Item = &'a HashedAbsolutePathRef<'a>;
+    type Item = &'a CloneFile;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             if let Some(path_clones_iter) = &mut self.path_clones_iter {
                 if let Some(clone) = path_clones_iter.next() {
-                    if let hash_map::Entry::Vacant(entry) = self.returned_hashes.entry(clone.hash())
-                    {
-                        entry.insert(());
+                    if self.returned.insert(clone.id()) {
                         return Some(clone);
                     } else {
                         continue; // item was already returned, continue consuming path_clones_iter
@@ -261,8 +260,11 @@ impl<'a> Iterator for PathsClonesIter<'a> {
 
             // path_clones_iter is None or next() returned None
             if let Some(path) = self.paths.get(self.path_index) {
-                self.path_clones_iter =
-                    Some(self.clone_groups.path_clones_iter_hap(path, self.recursive));
+                self.path_clones_iter = Some(self.clone_groups.path_clones_iter_hap(
+                    path,
+                    self.recursive,
+                    self.fs,
+                ));
                 self.path_index += 1;
             } else {
                 return None;
@@ -272,45 +274,91 @@ impl<'a> Iterator for PathsClonesIter<'a> {
 }
 
 pub struct FileClonesIter<'a> {
-    iter: Option<std::collections::btree_set::Iter<'a, HashedAbsolutePathRef<'a>>>,
-    orig_file_hash: u64,
+    clone_groups: &'a CloneGroups,
+    iter: Option<std::slice::Iter<'a, FileId>>,
+    orig_file_id: Option<FileId>,
 }
 
 impl<'a> Iterator for FileClonesIter<'a> {
-    type Item = &'a HashedAbsolutePathRef<'a>;
+    type Item = &'a CloneFile;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match &mut self.iter {
-            Some(iter) => iter.find(|file| file.hash() != self.orig_file_hash),
-            None => None,
-        }
+        let file_id = self
+            .iter
+            .as_mut()?
+            .find(|&&file_id| Some(file_id) != self.orig_file_id)?;
+        Some(self.clone_groups.file(*file_id))
     }
 }
 
 impl CloneGroups {
+    pub fn group(&self, group_id: GroupId) -> &CloneGroup {
+        &self.groups[group_id.0 as usize]
+    }
+
+    pub fn groups(&self) -> &[CloneGroup] {
+        &self.groups
+    }
+
+    pub fn file(&self, file_id: FileId) -> &CloneFile {
+        &self.files[file_id.0 as usize]
+    }
+
+    pub fn file_path(&self, file_id: FileId) -> &HashedAbsolutePath {
+        &self.file(file_id).path
+    }
+
+    /// returns the ID of the file with the specified interned path if it is a clone
+    pub fn path_id_file_id(&self, path_id: PathId) -> Option<FileId> {
+        self.path_files.get(&path_id).copied()
+    }
+
+    pub fn file_id<P: AsRef<Path>>(&self, file: P) -> Option<FileId> {
+        let file = file.as_ref().absolutize().unwrap();
+        self.path_id_file_id(PathId::get(file)?)
+    }
+
     pub fn file_is_a_clone<P: AsRef<Path>>(&self, file: P) -> bool {
-        let file = file.as_ref().absolutize().unwrap(); //.to_path_buf();
-        self.borrow_files().contains_key(file.as_ref())
+        self.file_id(file).is_some()
+    }
+
+    /// returns true if one of the other files of the group of the file is not a not-a-backup file,
+    /// otherwise the file is to be considered unique
+    pub fn file_has_backup(&self, file_id: FileId) -> bool {
+        self.file_id_clone_group(file_id)
+            .files
+            .iter()
+            .any(|&other_file_id| {
+                other_file_id != file_id && !self.file(other_file_id).not_a_backup
+            })
     }
 
     pub fn file_clones<P: AsRef<Path>>(&self, file: P) -> Option<FileClones> {
         self.file_clones_hap(HashedAbsolutePath::from(file.as_ref()))
     }
 
-    pub fn clone_group<P: AsRef<Path>>(&self, file: P) -> Option<&CloneRefGroup> {
-        self.borrow_files().get(file.as_ref()).copied()
+    pub fn clone_group<P: AsRef<Path>>(&self, file: P) -> Option<&CloneGroup> {
+        Some(self.group(self.file(self.file_id(file)?).group_id))
+    }
+
+    pub fn file_id_clone_group(&self, file_id: FileId) -> &CloneGroup {
+        self.group(self.file(file_id).group_id)
+    }
+
+    pub fn path_id_clone_group(&self, path_id: PathId) -> Option<&CloneGroup> {
+        Some(self.file_id_clone_group(self.path_id_file_id(path_id)?))
     }
 
     pub fn file_clones_hap<P: AsRef<HashedAbsolutePath>>(&self, file: P) -> Option<FileClones> {
-        let file = file.as_ref();
-        let ref_group = self.borrow_files().get(file.as_path())?;
-        let clones = ref_group
+        let file_id = self.path_id_file_id(file.as_ref().id())?;
+        let group = self.file_id_clone_group(file_id);
+        let clones = group
             .files
             .iter()
-            .filter(|&ifile| *ifile != file.to_absolute_path_ref())
-            .cloned()
+            .copied()
+            .filter(|&ifile| ifile != file_id)
             .collect();
-        Some(FileClones::new(ref_group.file_size, clones))
+        Some(FileClones::new(group.file_size, clones))
     }
 
     pub fn file_clones_iter<P: AsRef<Path>>(&self, file: P) -> FileClonesIter {
@@ -318,34 +366,38 @@ impl CloneGroups {
     }
 
     pub fn file_clones_iter_hap<P: AsRef<HashedAbsolutePath>>(&self, file: P) -> FileClonesIter {
-        let file = file.as_ref();
-        let ref_group = self.borrow_files().get(file.as_path());
+        let file_id = self.path_id_file_id(file.as_ref().id());
         FileClonesIter {
-            iter: ref_group.map(|rg| rg.files.iter()),
-            orig_file_hash: file.hash(),
+            clone_groups: self,
+            iter: file_id.map(|file_id| self.file_id_clone_group(file_id).files.iter()),
+            orig_file_id: file_id,
         }
     }
 
     pub fn files_iter(&self) -> CloneFilesIter {
-        CloneFilesIter {
-            groups_iter: self.borrow_ref_groups().iter(),
-            group_iter: None,
-        }
+        self.files.iter()
     }
 
-    pub fn dir_clone_files(&self, dir: impl AsRef<Path>, recursive: bool) -> DirCloneFiles {
-        self.dir_clone_files_hap(HashedAbsolutePath::from(dir.as_ref()), recursive)
+    pub fn dir_clone_files(
+        &self,
+        dir: impl AsRef<Path>,
+        recursive: bool,
+        bounds: &WalkBounds,
+    ) -> DirCloneFiles {
+        self.dir_clone_files_hap(HashedAbsolutePath::from(dir.as_ref()), recursive, bounds)
     }
 
     pub fn dir_clone_files_hap(
         &self,
         dir: impl AsRef<HashedAbsolutePath>,
         recursive: bool,
+        bounds: &WalkBounds,
     ) -> DirCloneFiles {
-        let dir_clone_groups = self.dir_clone_groups_hap(dir, recursive);
+        let dir_clone_groups = self.dir_clone_groups_hap(dir, recursive, bounds);
         let clone_files = dir_clone_groups
             .iter()
-            .map(|clones| clones.inside.files.clone())
+            .flat_map(|clones| clones.inside.files.iter().copied())
+            .sorted()
             .collect();
         let (total_size, reclaimable_count, reclaimable_size) =
             dir_clone_groups
@@ -381,57 +433,69 @@ impl CloneGroups {
         recursive: bool,
     ) -> DirCloneFilesIter {
         let dir = dir.as_ref();
-        let dir_hash = if dir.as_os_str() == "/" {
-            None
-        } else {
-            Some(dir.hash())
-        };
+        let first_file_index = self.files.partition_point(|file| file.path < *dir);
         DirCloneFilesIter {
-            files_iter: self.files_iter(),
-            dir_hash,
+            files_iter: self.files[first_file_index..].iter(),
+            dir_id: dir.id(),
             recursive,
         }
     }
 
-    pub fn path_clones_iter(&self, path: impl AsRef<Path>, recursive: bool) -> PathClonesIter {
-        self.path_clones_iter_hap(HashedAbsolutePath::from(path.as_ref()), recursive)
+    /// the path is looked up in `fs` to tell whether it is a directory or a file
+    pub fn path_clones_iter(
+        &self,
+        path: impl AsRef<Path>,
+        recursive: bool,
+        fs: &impl FileSystem,
+    ) -> PathClonesIter {
+        self.path_clones_iter_hap(HashedAbsolutePath::from(path.as_ref()), recursive, fs)
     }
 
     pub fn path_clones_iter_hap(
         &self,
         path: impl AsRef<HashedAbsolutePath>,
         recursive: bool,
+        fs: &(impl FileSystem + ?Sized),
     ) -> PathClonesIter {
         let path = path.as_ref();
-        if path.is_dir() {
+        let path_buf = path.to_path_buf();
+        if fs.is_dir(&path_buf) {
             PathClonesIter::Dir(self.dir_clone_files_iter_hap(path, recursive))
-        } else if path.is_file() {
+        } else if fs.is_file(&path_buf) {
             PathClonesIter::File(self.file_clones_iter_hap(path))
         } else {
             PathClonesIter::Other
         }
     }
 
+    /// the paths are looked up in `fs` to tell the directories from the files
     pub fn paths_clones_iter<'a>(
-        &self,
+        &'a self,
         paths: impl IntoIterator<Item = &'a str>,
         recursive: bool,
-    ) -> PathsClonesIter {
-        self.paths_clones_iter_hap(paths.into_iter().map(Into::into).collect_vec(), recursive)
+        fs: &'a dyn FileSystem,
+    ) -> PathsClonesIter<'a> {
+        self.paths_clones_iter_hap(
+            paths.into_iter().map(Into::into).collect_vec(),
+            recursive,
+            fs,
+        )
     }
 
-    pub fn paths_clones_iter_hap(
-        &self,
+    pub fn paths_clones_iter_hap<'a>(
+        &'a self,
         paths: impl IntoIterator<Item = HashedAbsolutePath>,
         recursive: bool,
-    ) -> PathsClonesIter {
+        fs: &'a dyn FileSystem,
+    ) -> PathsClonesIter<'a> {
         PathsClonesIter {
             clone_groups: self,
             paths: paths.into_iter().collect(),
             path_index: 0,
             path_clones_iter: None,
             recursive,
-            returned_hashes: HashMap::new(),
+            returned: HashSet::new(),
+            fs,
         }
     }
 
@@ -439,48 +503,69 @@ impl CloneGroups {
         &self,
         dir: impl AsRef<Path>,
         recursive: bool,
+        bounds: &WalkBounds,
     ) -> Vec<PartitionedDirClones> {
-        self.dir_clone_groups_hap(HashedAbsolutePath::from(dir.as_ref()), recursive)
+        self.dir_clone_groups_hap(HashedAbsolutePath::from(dir.as_ref()), recursive, bounds)
     }
 
     pub fn dir_clone_groups_hap(
         &self,
         dir: impl AsRef<HashedAbsolutePath>,
         recursive: bool,
+        bounds: &WalkBounds,
+    ) -> Vec<PartitionedDirClones> {
+        self.dirs_clone_groups_hap([dir.as_ref()], recursive, bounds)
+    }
+
+    /// partitions the clone groups having files in `dirs` with all of `dirs` together as the inside set,
+    /// so that a clone in one of the directories is an inside clone of the files of the others
+    /// the files of `dirs` out of `bounds`, counted from `dirs`, are outside clones
+    /// the not-a-backup files are left out of the outside clones and the inside files having no backup are left out
+    pub fn dirs_clone_groups_hap<'a>(
+        &self,
+        dirs: impl IntoIterator<Item = &'a HashedAbsolutePath>,
+        recursive: bool,
+        bounds: &WalkBounds,
     ) -> Vec<PartitionedDirClones> {
-        let ref_groups = self.borrow_ref_groups();
-        ref_groups
+        let dirs = dirs.into_iter().collect_vec();
+        let dir_paths = dirs.iter().map(|dir| dir.to_path_buf()).collect_vec();
+        let bounds = bounds.with_roots(dir_paths.iter().map(|dir| dir.as_path()));
+        let dirs_group_ids = dirs
             .iter()
-            .filter_map(|group| {
-                let (inside_dir, outside_dir) = group
-                    .iter()
-                    .partition::<Vec<&HashedAbsolutePathRef>, _>(|file| {
+            .flat_map(|dir| self.dir_clone_files_iter_hap(dir, recursive))
+            .map(CloneFile::group_id)
+            .collect::<BTreeSet<_>>();
+        dirs_group_ids
+            .into_iter()
+            .map(|group_id| {
+                let group = self.group(group_id);
+                let mut inside_dirs = vec![];
+                let mut outside_dirs = vec![];
+                for &file_id in &group.files {
+                    let file = self.file(file_id);
+                    let inside = dirs.iter().any(|dir| {
                         if recursive {
-                            file.starts_with_hashed_path(&dir)
+                            file.starts_with_id(dir.id())
                         } else {
-                            file.parent_is_hap(&dir)
+                            file.parent_id() == Some(dir.id())
                         }
-                    });
-
-                (!inside_dir.is_empty()).then(|| {
-                    let inside_dir = inside_dir
-                        .into_iter()
-                        .cloned()
-                        .collect::<HashedAbsolutePathRefSet>();
-                    let inside_clone_group = CloneRefGroup::from_parts(group.file_size, inside_dir);
-                    let outside_dir = outside_dir
-                        .into_iter()
-                        .cloned()
-                        .collect::<HashedAbsolutePathRefSet>();
-                    let outside_clone_group =
-                        CloneRefGroup::from_parts(group.file_size, outside_dir);
-                    PartitionedDirClones::new(
-                        group.file_size,
-                        inside_clone_group,
-                        outside_clone_group,
-                    )
-                })
+                    }) && bounds.contains(&file.to_path_buf());
+                    if inside {
+                        if self.file_has_backup(file_id) {
+                            inside_dirs.push(file_id);
+                        }
+                    } else if !file.not_a_backup {
+                        outside_dirs.push(file_id);
+                    }
+                }
+                PartitionedDirClones::new(
+                    group.file_size,
+                    CloneGroup::from_parts(group_id, group.file_size, inside_dirs),
+                    CloneGroup::from_parts(group_id, group.file_size, outside_dirs),
+                )
             })
+            // the groups whose files in `dirs` are all out of the bounds or have no backup
+            .filter(|clones| !clones.inside().is_empty())
             .collect()
     }
 }
@@ -504,26 +589,26 @@ impl DirCloneFilesStats {
 }
 
 #[derive(Debug, Deref, IntoIterator, Getters)]
-pub struct DirCloneFiles<'a> {
+pub struct DirCloneFiles {
     #[getset(get = "pub")]
     stats: DirCloneFilesStats,
     #[deref]
     #[into_iterator]
-    clones: HashedAbsolutePathRefSet<'a>,
+    clones: Vec<FileId>,
 }
 
-#[derive(Debug, Getters, CopyGetters)]
-pub struct PartitionedDirClones<'a> {
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct PartitionedDirClones {
     #[getset(get_copy = "pub")]
     file_size: u64,
     #[getset(get = "pub")]
-    inside: CloneRefGroup<'a>,
+    inside: CloneGroup,
     #[getset(get = "pub")]
-    outside: CloneRefGroup<'a>,
+    outside: CloneGroup,
 }
 
-impl<'a> PartitionedDirClones<'a> {
-    pub fn new(file_size: u64, inside: CloneRefGroup<'a>, outside: CloneRefGroup<'a>) -> Self {
+impl PartitionedDirClones {
+    pub fn new(file_size: u64, inside: CloneGroup, outside: CloneGroup) -> Self {
         Self {
             file_size,
             inside,
@@ -531,6 +616,10 @@ impl<'a> PartitionedDirClones<'a> {
         }
     }
 
+    pub fn group_id(&self) -> GroupId {
+        self.inside.id
+    }
+
     pub fn file_count(&self) -> usize {
         self.inside.len() + self.outside.len()
     }
@@ -566,36 +655,46 @@ impl<'a> PartitionedDirClones<'a> {
 
 impl FromIterator<(u64, Vec<HashedAbsolutePath>)> for CloneGroups {
     fn from_iter<T: IntoIterator<Item = (u64, Vec<HashedAbsolutePath>)>>(iter: T) -> Self {
-        Self::new(
-            iter.into_iter()
-                .map(|(file_size, files)| {
-                    let files = HashedAbsolutePathSet::from_iter(files);
-                    CloneGroup::from_parts(file_size, files).unwrap()
-                })
-                .collect(),
-            |groups| {
-                groups
-                    .iter()
-                    .map(|group| {
-                        let files = group
-                            .files()
-                            .iter()
-                            .map(HashedAbsolutePathRef::from)
-                            .collect();
-                        CloneRefGroup::from_parts(group.file_size, files)
-                    })
-                    .collect()
-            },
-            |ref_groups| {
-                let mut files = BTreeMap::new();
-                for group in ref_groups {
-                    for file in group.iter() {
-                        files.insert(file.as_ref(), group);
-                    }
-                }
-                files
-            },
-        )
+        let mut group_sizes = vec![];
+        let mut group_files = vec![];
+        for (group_index, (file_size, files)) in iter.into_iter().enumerate() {
+            let group_id = GroupId(u32::try_from(group_index).expect("too many clone groups"));
+            group_sizes.push(file_size);
+            group_files.extend(files.into_iter().map(|file| (file, group_id)));
+        }
+
+        // allocate file IDs in path order, a file listed in several groups is kept in the first one
+        group_files.sort_by(|(file1, group_id1), (file2, group_id2)| {
+            file1.cmp(file2).then(group_id1.cmp(group_id2))
+        });
+        group_files.dedup_by(|(file1, _), (file2, _)| file1 == file2);
+
+        let mut groups = group_sizes
+            .into_iter()
+            .enumerate()
+            .map(|(group_index, file_size)| {
+                CloneGroup::from_parts(GroupId(group_index as u32), file_size, vec![])
+            })
+            .collect_vec();
+        let mut files = Vec::with_capacity(group_files.len());
+        let mut path_files = HashMap::with_capacity(group_files.len());
+        for (file_index, (path, group_id)) in group_files.into_iter().enumerate() {
+            let id = FileId(u32::try_from(file_index).expect("too many clone files"));
+            groups[group_id.0 as usize].files.push(id);
+            path_files.insert(path.id(), id);
+            files.push(CloneFile {
+                id,
+                group_id,
+                path,
+                not_a_backup: false,
+            });
+        }
+
+        Self {
+            groups,
+            files,
+            path_files,
+        }
     }
 }
 
@@ -605,6 +704,15 @@ impl From<Vec<(u64, Vec<HashedAbsolutePath>)>> for CloneGroups {
     }
 }
 
+/// progress of the pruning of a clones list, the last call of each step has `processed` equal to `total`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneProgress {
+    /// checking that the scanned paths still exist
+    ScannedPaths { processed: usize, total: usize },
+    /// checking that the clone files still exist and did not change size
+    Files { processed: usize, total: usize },
+}
+
 #[derive(Debug, Getters, Deref)]
 #[getset(get = "pub")]
 pub struct ClonesDB {
@@ -614,76 +722,148 @@ pub struct ClonesDB {
 }
 
 impl ClonesDB {
-    /// reads clones database file in json format
-    pub fn read_clones_file<P: AsRef<Path>>(path: P, prune: bool) -> anyhow::Result<Self> {
-        log::info!(
-            "Loading clones list file: {}",
-            path.as_ref().to_string_lossy()
-        );
-        let file = File::open(path)?;
+    /// builds a clones database from (file size, files) clone groups without a scanned paths list
+    pub fn from_clone_groups(
+        clone_groups: impl IntoIterator<Item = (u64, Vec<HashedAbsolutePath>)>,
+    ) -> Self {
+        Self {
+            scanned_paths: None,
+            clone_groups: CloneGroups::from_iter(clone_groups),
+        }
+    }
 
-        if prune {
-            defer!(crossterm::execute!(io::stderr(), cursor::Show).unwrap());
-            crossterm::execute!(io::stderr(), cursor::Hide).unwrap();
+    /// marks the files for which `not_a_backup` returns true as not-a-backup files, they stay in their groups but
+    /// do not count as copies of the other files
+    pub fn mark_not_a_backup(&mut self, mut not_a_backup: impl FnMut(&Path) -> bool) {
+        for file in &mut self.clone_groups.files {
+            file.not_a_backup = not_a_backup(&file.path.to_path_buf());
         }
+    }
+
+    /// returns a database with `groups` as clone groups and the scanned paths and not-a-backup files of this one
+    fn rebuilt(&self, groups: Vec<(u64, Vec<HashedAbsolutePath>)>) -> Self {
+        let mut clone_groups = CloneGroups::from_iter(groups);
+        for file in &mut clone_groups.files {
+            file.not_a_backup = self
+                .path_id_file_id(file.path.id())
+                .is_some_and(|file_id| self.file(file_id).not_a_backup);
+        }
+        Self {
+            scanned_paths: self.scanned_paths.clone(),
+            clone_groups,
+        }
+    }
+
+    /// returns a copy of the database where the new files of `clones` are clones of the existing files,
+    /// the existing files which have no clone yet form new clone groups of `file_size`
+    pub fn with_clones(
+        &self,
+        clones: impl IntoIterator<Item = (HashedAbsolutePath, HashedAbsolutePath, u64)>,
+    ) -> Self {
+        let mut groups = self
+            .groups()
+            .iter()
+            .map(|group| (group.file_size(), group.paths(self).cloned().collect_vec()))
+            .collect_vec();
+        let mut new_groups: HashMap<PathId, usize> = HashMap::new();
+        for (file, clone, file_size) in clones {
+            let group_index = match self.path_id_file_id(file.id()) {
+                Some(file_id) => self.file(file_id).group_id().0 as usize,
+                None => *new_groups.entry(file.id()).or_insert_with(|| {
+                    groups.push((file_size, vec![file]));
+                    groups.len() - 1
+                }),
+            };
+            groups[group_index].1.push(clone);
+        }
+        self.rebuilt(groups)
+    }
+
+    /// returns a copy of the database without the files for which `exclude`, called with the path and size of the files,
+    /// returns true, the groups left with a single file are removed so that this file becomes unique
+    pub fn without_files(&self, mut exclude: impl FnMut(&Path, u64) -> bool) -> Self {
+        let groups = self
+            .groups()
+            .iter()
+            .map(|group| {
+                let files = group
+                    .paths(self)
+                    .filter(|file| !exclude(&file.to_path_buf(), group.file_size()))
+                    .cloned()
+                    .collect_vec();
+                (group.file_size(), files)
+            })
+            .filter(|(_, files)| files.len() > 1)
+            .collect_vec();
+        self.rebuilt(groups)
+    }
+
+    /// reads clones database file in json format
+    /// pruning checks the files in `fs` on `threads` threads and reports its progress to `progress`
+    pub fn read_clones_file<P: AsRef<Path>>(
+        path: P,
+        prune: bool,
+        fs: &impl FileSystem,
+        threads: NonZeroUsize,
+        mut progress: impl FnMut(PruneProgress),
+    ) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
 
         let mut scanned_paths = file.scanned_paths()?;
         if prune && scanned_paths.is_some() {
-            let progress_func = |(index, total): (usize, usize)| {
-                let percent = index * 100 / total;
-                bunt::eprint!(
-                    "\r{$green}INFO{/$}  {$bold}>{/$} Pruning scanned dirs list {} / {} ({}%)",
-                    index.to_formatted_string(&Locale::en),
-                    total.to_formatted_string(&Locale::en),
-                    percent
-                );
-                io::stderr().flush().unwrap();
-            };
-            let mut progress_display = CallRateLimiter::new(0.1, progress_func);
-            let scanned_paths_inner = scanned_paths.unwrap();
-            let scanned_path_count = scanned_paths_inner.len();
-            let mut scanned_paths_filtered = HashedAbsolutePathSet::default();
-            for (index, file) in scanned_paths_inner.iter().enumerate() {
-                if file.is_file() {
-                    scanned_paths_filtered.insert(file.clone());
-                }
-                progress_display.call((index, scanned_path_count));
-            }
-            progress_display.call_unconditional((scanned_path_count, scanned_path_count));
+            let scanned_paths_inner = scanned_paths.unwrap().iter().cloned().collect_vec();
+            let total = scanned_paths_inner.len();
+            let exists = parallel::map_with_progress(
+                &scanned_paths_inner,
+                threads,
+                |path| fs.exists(&path.to_path_buf()),
+                |processed| progress(PruneProgress::ScannedPaths { processed, total }),
+            );
+            progress(PruneProgress::ScannedPaths {
+                processed: total,
+                total,
+            });
+            let scanned_paths_filtered = scanned_paths_inner
+                .into_iter()
+                .zip(exists)
+                .filter_map(|(path, exists)| exists.then_some(path))
+                .collect();
             scanned_paths = Some(scanned_paths_filtered);
         }
 
         let mut clone_groups = file.clone_groups()?;
         if prune {
-            let progress_func = |(index, total): (usize, usize)| {
-                let percent = index * 100 / total;
-                bunt::eprint!(
-                    "\r{$green}INFO{/$}  {$bold}>{/$} Pruning clone files list {} / {} ({}%)",
-                    index.to_formatted_string(&Locale::en),
-                    total.to_formatted_string(&Locale::en),
-                    percent
-                );
-                io::stderr().flush().unwrap();
-            };
-            let mut progress_display = CallRateLimiter::new(0.1, progress_func);
-            let file_count: usize = clone_groups.iter().map(|(_, group)| group.len()).sum();
+            let files = clone_groups
+                .iter()
+                .flat_map(|(size, group)| group.iter().map(move |file| (*size, file)))
+                .collect_vec();
+            let total = files.len();
+            // a file which changed size since the clones list has been generated is not a clone anymore
+            let keep = parallel::map_with_progress(
+                &files,
+                threads,
+                |(size, file)| {
+                    fs.metadata(&file.to_path_buf()).is_ok_and(|metadata| {
+                        metadata.is_file() && metadata.metadata().size() == *size
+                    })
+                },
+                |processed| progress(PruneProgress::Files { processed, total }),
+            );
+            let mut keep = keep.into_iter();
             let mut clone_groups_filtered = vec![];
-            let mut index = 0;
             for (size, group) in clone_groups {
-                let mut group_filtered = vec![];
-                for file in group {
-                    if file.is_file() {
-                        group_filtered.push(file)
-                    }
-                    progress_display.call((index, file_count));
-                    index += 1;
-                }
+                let group_filtered = group
+                    .into_iter()
+                    .filter(|_| keep.next().unwrap())
+                    .collect_vec();
                 if group_filtered.len() > 1 {
                     clone_groups_filtered.push((size, group_filtered));
                 }
             }
-            progress_display.call_unconditional((file_count, file_count));
-            eprintln!();
+            progress(PruneProgress::Files {
+                processed: total,
+                total,
+            });
             clone_groups = clone_groups_filtered;
         }
 
@@ -693,6 +873,17 @@ impl ClonesDB {
         })
     }
 
+    /// returns false if `path` is outside of the paths scanned to build the clones list, in which case
+    /// its clones are unknown, always true when the clones list does not record the scanned paths
+    pub fn was_scanned(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.scanned_paths.as_ref().map_or(true, |scanned_paths| {
+            scanned_paths
+                .iter()
+                .any(|scanned_path| path.starts_with(scanned_path.to_path_buf()))
+        })
+    }
+
     // /// returns an error if the specified path is not part of the directories scanned during the clones database's construction
     // fn check_part_of_scanned_paths<P: AsRef<Path> + std::fmt::Display>(&self, path: P) -> anyhow::Result<()> {
     //     if ! self.scanned_paths.contains_parent_of(&path) {
@@ -701,3 +892,97 @@ impl ClonesDB {
     //     Ok(())
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::vfs::MemoryFileSystem;
+
+    #[test]
+    fn dirs_clone_groups_single_scope() {
+        let clones_db = ClonesDB::from_clone_groups([(
+            10,
+            ["/project/a/f", "/project/b/f", "/elsewhere/f"]
+                .map(HashedAbsolutePath::from)
+                .to_vec(),
+        )]);
+        let dirs = ["/project/a", "/project/b"].map(HashedAbsolutePath::from);
+        let clone_groups = clones_db.dirs_clone_groups_hap(&dirs, true, &WalkBounds::default());
+        assert_eq!(clone_groups.len(), 1);
+        assert_eq!(clone_groups[0].inside().len(), 2);
+        assert_eq!(clone_groups[0].outside().len(), 1);
+        let clone_groups = clones_db.dir_clone_groups_hap(dirs[0], true, &WalkBounds::default());
+        assert_eq!(clone_groups[0].outside().len(), 2);
+    }
+
+    #[test]
+    fn dirs_clone_groups_bounds() {
+        let clones_db = ClonesDB::from_clone_groups([(
+            10,
+            ["/project/f", "/project/a/f", "/project/a/b/f"]
+                .map(HashedAbsolutePath::from)
+                .to_vec(),
+        )]);
+        // the clones list files deeper than the bounds are outside clones
+        let bounds = WalkBounds::new(None, Some(2), false);
+        let clone_groups = clones_db.dir_clone_groups(Path::new("/project"), true, &bounds);
+        assert_eq!(clone_groups[0].inside().len(), 2);
+        assert_eq!(
+            clones_db
+                .file_path(clone_groups[0].outside()[0])
+                .to_path_buf(),
+            Path::new("/project/a/b/f")
+        );
+    }
+
+    #[test]
+    fn not_a_backup_clones() {
+        let mut clones_db = ClonesDB::from_clone_groups([
+            (
+                10,
+                ["/tmp/x/f", "/home/f"]
+                    .map(HashedAbsolutePath::from)
+                    .to_vec(),
+            ),
+            (
+                20,
+                ["/tmp/g", "/home/g"].map(HashedAbsolutePath::from).to_vec(),
+            ),
+        ]);
+        clones_db.mark_not_a_backup(|file| file.starts_with("/tmp"));
+        // the clones of a not-a-backup file are still listed
+        let clone_groups =
+            clones_db.dir_clone_groups(Path::new("/tmp/x"), true, &WalkBounds::default());
+        assert_eq!(clone_groups.len(), 1);
+        assert_eq!(clone_groups[0].outside().len(), 1);
+        // a file whose only copy is a not-a-backup file has no clone
+        let clone_groups =
+            clones_db.dir_clone_groups(Path::new("/home"), true, &WalkBounds::default());
+        assert!(clone_groups.is_empty());
+        assert!(!clones_db.file_has_backup(clones_db.file_id("/home/g").unwrap()));
+        assert!(clones_db.file_has_backup(clones_db.file_id("/tmp/g").unwrap()));
+    }
+
+    #[test]
+    fn prune_scanned_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let clones_file = temp_dir.path().join("clones.json");
+        std::fs::write(
+            &clones_file,
+            r#"{"header": {"base_dir": "/", "paths": ["/fixture", "/removed"]}, "groups": [
+                {"file_len": 10, "files": ["/fixture/a/f", "/fixture/b/f", "/removed/f"]}
+            ]}"#,
+        )
+        .unwrap();
+        let mut fs = MemoryFileSystem::default();
+        fs.insert_file("/fixture/a/f", 10);
+        fs.insert_file("/fixture/b/f", 10);
+        let clones_db =
+            ClonesDB::read_clones_file(&clones_file, true, &fs, NonZeroUsize::MIN, |_| {}).unwrap();
+        // the scanned directory still existing is kept
+        assert_eq!(clones_db.scanned_paths().as_ref().unwrap().len(), 1);
+        assert!(clones_db.was_scanned("/fixture/a/f"));
+        assert!(!clones_db.was_scanned("/removed/f"));
+        assert_eq!(clones_db.groups()[0].len(), 2);
+    }
+}
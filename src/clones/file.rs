@@ -17,7 +17,7 @@ pub struct File {
 impl File {
     /// reads clones database file in json format
     pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let file = fs_err::File::open(&path)?;
+        let file = fs_err::File::open(path.as_ref())?;
         let file_buf = io::BufReader::new(file);
         let content: HashMap<String, serde_json::Value> = serde_json::from_reader(file_buf)
             .map_err(|err| {
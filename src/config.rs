@@ -0,0 +1,51 @@
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use getset::Getters;
+use serde::Deserialize;
+
+fn config_dir() -> anyhow::Result<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_dir) if !config_dir.is_empty() => PathBuf::from(config_dir),
+        _ => env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".config"))
+            .ok_or_else(|| anyhow!("cannot determine the config directory: HOME is not set"))?,
+    };
+    Ok(config_dir.join("lsclones"))
+}
+
+/// returns `$XDG_CONFIG_HOME/lsclones/config.json` or `~/.config/lsclones/config.json`
+pub fn default_config_path() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("config.json"))
+}
+
+/// returns the path of the global ignore file: `$XDG_CONFIG_HOME/lsclones/ignore` or `~/.config/lsclones/ignore`
+pub fn default_ignore_path() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("ignore"))
+}
+
+/// settings read from the configuration file, in JSON format
+#[derive(Debug, Clone, Default, Deserialize, Getters)]
+#[serde(default, deny_unknown_fields)]
+#[getset(get = "pub")]
+pub struct Config {
+    /// glob patterns of the locations, such as `/tmp` or trash directories, whose files never count as copies
+    not_a_backup: Vec<String>,
+}
+
+impl Config {
+    /// reads the configuration file, the default configuration is returned if the file does not exist
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        match fs_err::File::open(path) {
+            Ok(file) => serde_json::from_reader(io::BufReader::new(file)).map_err(|error| {
+                anyhow!("invalid config file {}: {error}", path.to_string_lossy())
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    catalog::hash_file, clones::db::ClonesDB, error_behavior::ErrorBehavior, fs,
+    path::HashedAbsolutePath,
+};
+
+/// blocking file copied or moved out of the consolidated directory
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct TransferredFile {
+    #[getset(get = "pub")]
+    file: PathBuf,
+    #[getset(get = "pub")]
+    destination: PathBuf,
+    #[getset(get_copy = "pub")]
+    size: u64,
+}
+
+/// copies `file` to `destination`, creating the missing directories, and checks that the copy has the same content,
+/// with `move_file` the file is removed once the copy has been checked
+pub fn transfer_file(file: &Path, destination: &Path, move_file: bool) -> anyhow::Result<u64> {
+    if destination.symlink_metadata().is_ok() {
+        return Err(anyhow!(
+            "destination already exists: {}",
+            destination.to_string_lossy()
+        ));
+    }
+    if let Some(parent) = destination.parent() {
+        fs_err::create_dir_all(parent)?;
+    }
+    let size = fs_err::copy(file, destination)?;
+    if hash_file(file)? != hash_file(destination)? {
+        fs_err::remove_file(destination)?;
+        return Err(anyhow!(
+            "the copy of {} differs from the original",
+            file.to_string_lossy()
+        ));
+    }
+    if move_file {
+        fs_err::remove_file(file)?;
+    }
+    Ok(size)
+}
+
+/// copies, or moves with `move_files`, the files preventing `dir` from being a clone dir to the same relative
+/// path in `target`, `progress` is called after each file
+pub fn consolidate(
+    file_tree: &fs::Tree,
+    dir: impl AsRef<Path>,
+    target: impl AsRef<Path>,
+    clones_db: &ClonesDB,
+    move_files: bool,
+    error_behavior: ErrorBehavior,
+    mut progress: impl FnMut(&TransferredFile),
+) -> anyhow::Result<Vec<TransferredFile>> {
+    let dir = dir.as_ref();
+    let target = target.as_ref();
+    let dir_path = file_tree.node_with_path(dir)?.path();
+    if target.starts_with(&dir_path) {
+        return Err(anyhow!(
+            "the target cannot be inside the consolidated directory: {}",
+            target.to_string_lossy()
+        ));
+    }
+    let mut transferred_files = vec![];
+    for file in file_tree.dir_blocking_files(dir, clones_db)? {
+        let destination = target.join(file.strip_prefix(&dir_path).unwrap());
+        let result = transfer_file(&file, &destination, move_files);
+        if let Some(size) = error_behavior.handle(result, &file, "failed to transfer")? {
+            let transferred_file = TransferredFile {
+                file,
+                destination,
+                size,
+            };
+            progress(&transferred_file);
+            transferred_files.push(transferred_file);
+        }
+    }
+    Ok(transferred_files)
+}
+
+/// returns the clones database updated with the transferred files
+pub fn updated_clones_db(clones_db: &ClonesDB, transferred_files: &[TransferredFile]) -> ClonesDB {
+    clones_db.with_clones(transferred_files.iter().map(|transferred_file| {
+        (
+            HashedAbsolutePath::from(transferred_file.file.as_path()),
+            HashedAbsolutePath::from(transferred_file.destination.as_path()),
+            transferred_file.size,
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::*;
+    use crate::{
+        fs::vfs::RealFileSystem,
+        path::filter::PathFilter,
+        paths::{PathRefs, TreeWithProgress},
+    };
+
+    #[test]
+    fn consolidate_dir() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let (dir, target) = (root.join("dir"), root.join("target"));
+        fs_err::create_dir_all(dir.join("sub")).unwrap();
+        fs_err::create_dir_all(root.join("elsewhere")).unwrap();
+        std::fs::write(dir.join("clone"), "clone").unwrap();
+        std::fs::write(root.join("elsewhere/clone"), "clone").unwrap();
+        std::fs::write(dir.join("sub/unique"), "unique").unwrap();
+        let clones_db = ClonesDB::from_clone_groups([(
+            5,
+            vec![
+                HashedAbsolutePath::from(dir.join("clone")),
+                HashedAbsolutePath::from(root.join("elsewhere/clone")),
+            ],
+        )]);
+        let tree = PathRefs::new(vec![dir.as_path()])
+            .tree_with_progress(
+                &RealFileSystem,
+                ErrorBehavior::Stop,
+                NonZeroUsize::MIN,
+                None,
+                &PathFilter::default(),
+                |_, _| {},
+            )
+            .unwrap();
+        let transferred_files = consolidate(
+            &tree,
+            &dir,
+            &target,
+            &clones_db,
+            false,
+            ErrorBehavior::Stop,
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(transferred_files.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(target.join("sub/unique")).unwrap(),
+            "unique"
+        );
+        let clones_db = updated_clones_db(&clones_db, &transferred_files);
+        assert!(tree
+            .dir_blocking_files(&dir, &clones_db)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,3 +1,6 @@
+use std::{fmt::Display, path::Path};
+
+use anyhow::anyhow;
 use clap::ValueEnum;
 use strum::Display;
 
@@ -7,3 +10,34 @@ pub enum ErrorBehavior {
     Display,
     Stop,
 }
+
+impl ErrorBehavior {
+    /// returns the value of `result` or None if it is an error which should not stop the processing
+    /// the error is prefixed with `error_message` and `path` when it is displayed or returned
+    pub fn handle<T, E: Display>(
+        self,
+        result: Result<T, E>,
+        path: impl AsRef<Path>,
+        error_message: &str,
+    ) -> anyhow::Result<Option<T>> {
+        use ErrorBehavior::*;
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => match self {
+                Ignore => Ok(None),
+                Display | Stop => {
+                    let error_string =
+                        format!("{error_message} `{}`: {e}", path.as_ref().to_string_lossy());
+                    match self {
+                        Display => {
+                            eprintln!("{error_string}");
+                            Ok(None)
+                        }
+                        Stop => Err(anyhow!("{error_string}")),
+                        _ => unreachable!(),
+                    }
+                }
+            },
+        }
+    }
+}
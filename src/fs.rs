@@ -1,4 +1,10 @@
+pub mod attribute_filter;
 pub mod dir;
+pub mod symlinks;
+#[cfg(test)]
+pub(crate) mod test_fixture;
 pub mod tree;
+pub mod vfs;
+pub mod walk_bounds;
 
 pub use tree::FSTree as Tree;
@@ -0,0 +1,137 @@
+use std::{path::Path, time::SystemTime};
+
+use clap::ValueEnum;
+use derive_more::Constructor;
+use strum::Display;
+
+use super::tree::metadata::NodeMetadata;
+
+/// categories of files recognized by their extension
+#[derive(Copy, Clone, Display, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[strum(serialize_all = "lowercase")]
+pub enum FileType {
+    Image,
+    Video,
+    Audio,
+    Archive,
+    Document,
+}
+
+impl FileType {
+    /// lowercase extensions of the files of this type
+    pub fn extensions(self) -> &'static [&'static str] {
+        use FileType::*;
+        match self {
+            Image => &[
+                "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif", "svg",
+                "raw", "cr2", "cr3", "nef", "arw", "dng", "orf", "rw2",
+            ],
+            Video => &[
+                "mp4", "m4v", "mkv", "avi", "mov", "wmv", "flv", "webm", "mpg", "mpeg", "m2ts",
+                "mts", "ts", "3gp", "vob",
+            ],
+            Audio => &[
+                "mp3", "flac", "wav", "ogg", "oga", "opus", "m4a", "aac", "wma", "aiff", "ape",
+                "alac",
+            ],
+            Archive => &[
+                "zip", "tar", "gz", "tgz", "bz2", "tbz2", "xz", "txz", "zst", "7z", "rar", "iso",
+                "img", "dmg",
+            ],
+            Document => &[
+                "pdf", "doc", "docx", "odt", "xls", "xlsx", "ods", "ppt", "pptx", "odp", "txt",
+                "md", "rtf", "epub",
+            ],
+        }
+    }
+
+    /// returns true if the extension of `file` is one of the extensions of this type
+    pub fn matches(self, file: impl AsRef<Path>) -> bool {
+        file.as_ref()
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .is_some_and(|extension| self.extensions().contains(&extension.as_str()))
+    }
+}
+
+/// size, modification time and type criteria selecting the files to analyse
+#[derive(Debug, Clone, Default, Constructor)]
+pub struct AttributeFilter {
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    newer_than: Option<SystemTime>,
+    older_than: Option<SystemTime>,
+    types: Vec<FileType>,
+}
+
+impl AttributeFilter {
+    pub fn is_empty(&self) -> bool {
+        !self.has_size_criteria() && !self.has_time_criteria() && self.types.is_empty()
+    }
+
+    pub fn has_size_criteria(&self) -> bool {
+        self.min_size.is_some() || self.max_size.is_some()
+    }
+
+    /// the modification time is only known for the walked files, not for the files of the clones list
+    pub fn has_time_criteria(&self) -> bool {
+        self.newer_than.is_some() || self.older_than.is_some()
+    }
+
+    /// returns true if the file is of one of the types when there are some
+    pub fn accepts_type(&self, file: impl AsRef<Path>) -> bool {
+        let file = file.as_ref();
+        self.types.is_empty() || self.types.iter().any(|file_type| file_type.matches(file))
+    }
+
+    pub fn accepts_size(&self, size: u64) -> bool {
+        !self.min_size.is_some_and(|min_size| size < min_size)
+            && !self.max_size.is_some_and(|max_size| size > max_size)
+    }
+
+    pub fn accepts_modified(&self, modified: SystemTime) -> bool {
+        !self
+            .newer_than
+            .is_some_and(|newer_than| modified <= newer_than)
+            && !self
+                .older_than
+                .is_some_and(|older_than| modified >= older_than)
+    }
+
+    /// returns true if the size and modification time recorded while walking the file are accepted
+    pub fn accepts_metadata(&self, metadata: &NodeMetadata) -> bool {
+        self.accepts_size(metadata.size()) && self.accepts_modified(metadata.modified())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn accepts_attributes() {
+        let now = SystemTime::now();
+        let filter = AttributeFilter::new(
+            Some(1000),
+            None,
+            Some(now - Duration::from_secs(3600)),
+            None,
+            vec![FileType::Image, FileType::Video],
+        );
+        assert!(filter.accepts_type("/photos/IMG_0001.JPG"));
+        assert!(filter.accepts_type("/videos/a.mkv"));
+        assert!(!filter.accepts_type("/docs/a.pdf"));
+        assert!(!filter.accepts_type("/photos/jpg"));
+        assert!(filter.accepts_metadata(&NodeMetadata::new(1000, now, 1, 1)));
+        assert!(!filter.accepts_metadata(&NodeMetadata::new(999, now, 1, 1)));
+        assert!(!filter.accepts_metadata(&NodeMetadata::new(
+            1000,
+            now - Duration::from_secs(7200),
+            1,
+            1
+        )));
+        assert!(AttributeFilter::default().is_empty());
+    }
+}
@@ -1,7 +1,6 @@
 use std::{
     collections::VecDeque,
-    fs,
-    os::unix::prelude::MetadataExt,
+    num::NonZeroUsize,
     path::{Path, PathBuf},
 };
 
@@ -9,7 +8,7 @@ use anyhow::anyhow;
 
 use crate::{clones::db::ClonesDB, error_behavior::ErrorBehavior, paths::Paths};
 
-use super::Tree;
+use super::{vfs::FileSystem, Tree};
 
 // pub fn files<P: AsRef<StdPath>>(path: P) -> anyhow::Result<Paths> {
 //     let entry_iter = fs::read_dir(&path).map_err(|error|
@@ -27,15 +26,17 @@ use super::Tree;
 //     Ok(files)
 // }
 
-pub struct DirWalker {
+pub struct DirWalker<'a, F: FileSystem> {
+    fs: &'a F,
     dirs_to_process: VecDeque<PathBuf>,
     current_dir_entries: Vec<PathBuf>,
     error_behavior: ErrorBehavior,
 }
 
-impl DirWalker {
-    pub fn new(dir: impl Into<PathBuf>, error_behavior: ErrorBehavior) -> Self {
+impl<'a, F: FileSystem> DirWalker<'a, F> {
+    pub fn new(fs: &'a F, dir: impl Into<PathBuf>, error_behavior: ErrorBehavior) -> Self {
         Self {
+            fs,
             dirs_to_process: VecDeque::from_iter(Some(dir.into())),
             current_dir_entries: vec![],
             error_behavior,
@@ -43,7 +44,7 @@ impl DirWalker {
     }
 }
 
-impl Iterator for DirWalker {
+impl<F: FileSystem> Iterator for DirWalker<'_, F> {
     type Item = anyhow::Result<PathBuf>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -54,8 +55,8 @@ impl Iterator for DirWalker {
 
             use ErrorBehavior::*;
             if let Some(dir) = self.dirs_to_process.pop_back() {
-                let dir_iter = match std::fs::read_dir(&dir) {
-                    Ok(dir_iter) => dir_iter,
+                let dir_entries = match self.fs.read_dir(&dir) {
+                    Ok(dir_entries) => dir_entries,
                     Err(e) => match self.error_behavior {
                         Ignore => continue,
                         Display | Stop => {
@@ -72,14 +73,14 @@ impl Iterator for DirWalker {
                         }
                     },
                 };
-                for entry in dir_iter {
+                for entry in dir_entries {
                     match entry {
-                        Ok(entry) => match entry.file_type() {
-                            Ok(file_type) => {
-                                if file_type.is_file() {
-                                    self.current_dir_entries.push(entry.path());
-                                } else if file_type.is_dir() {
-                                    self.dirs_to_process.push_front(entry.path());
+                        Ok(entry) => match entry.metadata {
+                            Ok(metadata) => {
+                                if metadata.is_file() {
+                                    self.current_dir_entries.push(entry.path);
+                                } else if metadata.is_dir() {
+                                    self.dirs_to_process.push_front(entry.path);
                                 }
                             }
                             Err(e) => match self.error_behavior {
@@ -87,7 +88,7 @@ impl Iterator for DirWalker {
                                 Display | Stop => {
                                     let error_string = format!(
                                         "failed to get file type of `{}`: `{e}",
-                                        entry.path().to_string_lossy()
+                                        entry.path.to_string_lossy()
                                     );
                                     match self.error_behavior {
                                         Display => {
@@ -110,55 +111,53 @@ impl Iterator for DirWalker {
     }
 }
 
-pub fn dirs<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<PathBuf>> {
-    let entry_iter = fs::read_dir(&path).map_err(|error| {
+pub fn dirs<P: AsRef<Path>>(fs: &impl FileSystem, path: P) -> anyhow::Result<Vec<PathBuf>> {
+    let entries = fs.read_dir(path.as_ref()).map_err(|error| {
         anyhow!(
             "failed to read directory `{}`: {error}",
             path.as_ref().to_string_lossy()
         )
     })?;
-    let mut files = vec![];
-    for entry in entry_iter {
-        let entry_path = entry
-            .map_err(|error| {
-                anyhow!(
-                    "failed reading entry in directory `{}`: {error}",
-                    path.as_ref().to_string_lossy()
-                )
-            })?
-            .path();
-        if entry_path.is_dir() {
-            files.push(entry_path);
+    let mut dirs = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|error| {
+            anyhow!(
+                "failed reading entry in directory `{}`: {error}",
+                path.as_ref().to_string_lossy()
+            )
+        })?;
+        // the symbolic links are only listed as directories when `fs` follows them, after checking for loops
+        if entry
+            .metadata
+            .as_ref()
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            dirs.push(entry.path);
         }
     }
-    Ok(files)
+    Ok(dirs)
 }
 
-pub struct FilesWalker(walkdir::IntoIter);
+pub struct FilesWalker {
+    iter: walkdir::IntoIter,
+    error_behavior: ErrorBehavior,
+}
 
 impl Iterator for FilesWalker {
-    type Item = PathBuf;
+    type Item = anyhow::Result<PathBuf>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            match self.0.next()? {
+            match self.iter.next()? {
                 Ok(entry) => {
                     let entry_path = entry.path();
                     if entry_path.is_file() {
-                        return Some(entry_path.to_path_buf());
+                        return Some(Ok(entry_path.to_path_buf()));
                     }
                 }
                 Err(error) => {
-                    if let Some(path) = error.path() {
-                        let error_str = match error.io_error() {
-                            Some(error) => error.to_string(),
-                            None => "unknown".to_owned(),
-                        };
-                        eprintln!(
-                            "error listing files from `{}`: {}",
-                            path.to_string_lossy(),
-                            error_str
-                        );
+                    if let Err(error) = handle_walk_error(error, self.error_behavior) {
+                        return Some(Err(error));
                     }
                 }
             }
@@ -166,21 +165,26 @@ impl Iterator for FilesWalker {
     }
 }
 
-pub fn walk_files<P: AsRef<Path>>(path: P) -> FilesWalker {
-    FilesWalker(walkdir::WalkDir::new(path).into_iter())
+/// the listing errors are returned, displayed or skipped according to `error_behavior`
+pub fn walk_files<P: AsRef<Path>>(path: P, error_behavior: ErrorBehavior) -> FilesWalker {
+    FilesWalker {
+        iter: walkdir::WalkDir::new(path).into_iter(),
+        error_behavior,
+    }
 }
 
-pub fn files_rec<P: AsRef<Path>>(path: P) -> Paths {
-    walk_files(path).collect()
+pub fn files_rec<P: AsRef<Path>>(path: P, error_behavior: ErrorBehavior) -> anyhow::Result<Paths> {
+    walk_files(path, error_behavior).collect()
 }
 
 pub struct DirsWalker {
     first: bool,
     iter: walkdir::IntoIter,
+    error_behavior: ErrorBehavior,
 }
 
 impl Iterator for DirsWalker {
-    type Item = PathBuf;
+    type Item = anyhow::Result<PathBuf>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -192,20 +196,12 @@ impl Iterator for DirsWalker {
                     }
                     let entry_path = entry.path();
                     if entry_path.is_dir() {
-                        return Some(entry_path.to_path_buf());
+                        return Some(Ok(entry_path.to_path_buf()));
                     }
                 }
                 Err(error) => {
-                    if let Some(path) = error.path() {
-                        let error_str = match error.io_error() {
-                            Some(error) => error.to_string(),
-                            None => "unknown".to_owned(),
-                        };
-                        eprintln!(
-                            "error listing files from `{}`: {}",
-                            path.to_string_lossy(),
-                            error_str
-                        );
+                    if let Err(error) = handle_walk_error(error, self.error_behavior) {
+                        return Some(Err(error));
                     }
                 }
             }
@@ -213,24 +209,38 @@ impl Iterator for DirsWalker {
     }
 }
 
-pub fn walk_dirs<P: AsRef<Path>>(path: P) -> DirsWalker {
+/// the listing errors are returned, displayed or skipped according to `error_behavior`
+pub fn walk_dirs<P: AsRef<Path>>(path: P, error_behavior: ErrorBehavior) -> DirsWalker {
     DirsWalker {
         first: true,
         iter: walkdir::WalkDir::new(path).into_iter(),
+        error_behavior,
     }
 }
 
+fn handle_walk_error(error: walkdir::Error, error_behavior: ErrorBehavior) -> anyhow::Result<()> {
+    let path = error.path().map(Path::to_path_buf).unwrap_or_default();
+    let error_str = error
+        .io_error()
+        .map_or_else(|| error.to_string(), ToString::to_string);
+    error_behavior.handle(Err::<(), _>(error_str), path, "error listing files from")?;
+    Ok(())
+}
+
 // pub fn dirs_rec<P: AsRef<Path>>(path: P) -> Paths {
 //     walk_dirs(path).collect()
 // }
 
 /// returns true if the specified directory only contains uniq files
 pub fn is_unique_dir<P: AsRef<Path>>(dir: P, clones_db: &ClonesDB) -> bool {
-    clones_db.dir_clone_files_iter(dir, true).next().is_none()
+    !clones_db
+        .dir_clone_files_iter(dir, true)
+        .any(|file| clones_db.file_has_backup(file.id()))
 }
 
 // /// returns dirs which only contain uniq files
 pub fn unique_dirs<P: Into<PathBuf>>(
+    fs: &impl FileSystem,
     dir: P,
     recursive: bool,
     clones_db: &ClonesDB,
@@ -243,12 +253,12 @@ pub fn unique_dirs<P: Into<PathBuf>>(
             if is_unique_dir(&current_dir, clones_db) {
                 unique_dirs.push(current_dir);
             } else {
-                dirs_to_process.extend(dirs(current_dir)?.into_iter());
+                dirs_to_process.extend(dirs(fs, current_dir)?.into_iter());
             }
         }
         Ok(unique_dirs)
     } else {
-        Ok(dirs(dir)?
+        Ok(dirs(fs, dir)?
             .into_iter()
             .filter(|idir| is_unique_dir(idir, clones_db))
             .collect())
@@ -256,14 +266,19 @@ pub fn unique_dirs<P: Into<PathBuf>>(
 }
 
 /// returns directory size in bytes
-pub fn size<P: AsRef<Path>>(dir: P) -> u64 {
-    walk_files(dir)
-        .map(|file| std::fs::metadata(file).unwrap().size())
+pub fn size<P: AsRef<Path>>(fs: &impl FileSystem, dir: P) -> u64 {
+    DirWalker::new(fs, dir.as_ref(), ErrorBehavior::Display)
+        .filter_map(Result::ok)
+        .map(|file| fs.metadata(&file).unwrap().metadata().size())
         .sum()
 }
 
-pub fn file_tree(dir: impl AsRef<Path>, error_behavior: ErrorBehavior) -> anyhow::Result<Tree> {
+pub fn file_tree(
+    dir: impl AsRef<Path>,
+    error_behavior: ErrorBehavior,
+    threads: NonZeroUsize,
+) -> anyhow::Result<Tree> {
     let mut tree = Tree::default();
-    tree.extend_with_progress(dir, error_behavior, |_, _| {})?;
+    tree.extend(dir, error_behavior, threads)?;
     Ok(tree)
 }
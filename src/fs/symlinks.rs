@@ -0,0 +1,134 @@
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use getset::Getters;
+use strum::Display;
+
+use crate::error_behavior::ErrorBehavior;
+
+use super::vfs::FileSystem;
+
+/// what the walks do with the symbolic links
+#[derive(Copy, Clone, Display, Debug, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum SymlinkPolicy {
+    /// leave the symbolic links out
+    #[default]
+    Ignore,
+    /// walk the targets of the symbolic links as if they were in place of the links, except those looping back
+    Follow,
+    /// leave the symbolic links out of the walks and list the dangling ones and the ones pointing into the listed directories
+    Report,
+}
+
+/// symbolic link found while walking a directory
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+pub struct Symlink {
+    path: PathBuf,
+    /// target as stored in the link
+    target: PathBuf,
+    /// canonical path of the target, None when it does not exist
+    resolved_target: Option<PathBuf>,
+}
+
+impl Symlink {
+    pub fn is_dangling(&self) -> bool {
+        self.resolved_target.is_none()
+    }
+
+    /// returns true if the target exists and is `dir`, a canonical path, or inside of it
+    pub fn points_into(&self, dir: &Path) -> bool {
+        self.resolved_target
+            .as_ref()
+            .is_some_and(|target| target.starts_with(dir))
+    }
+}
+
+/// lists the symbolic links in `dir` recursively, the directories being listed from `fs`
+pub fn find_symlinks(
+    fs: &impl FileSystem,
+    dir: impl Into<PathBuf>,
+    error_behavior: ErrorBehavior,
+) -> anyhow::Result<Vec<Symlink>> {
+    let mut dirs_to_process = vec![dir.into()];
+    let mut symlinks = vec![];
+    while let Some(dir) = dirs_to_process.pop() {
+        let Some(entries) = error_behavior.handle(fs.read_dir(&dir), &dir, "failed reading")?
+        else {
+            continue;
+        };
+        for entry in entries {
+            let Some(entry) = error_behavior.handle(entry, &dir, "failed reading entry in")? else {
+                continue;
+            };
+            let Some(metadata) =
+                error_behavior.handle(entry.metadata, &entry.path, "failed to get file type of")?
+            else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs_to_process.push(entry.path);
+            } else if metadata.kind().is_none() {
+                // the other entries which are not symbolic links, such as sockets, cannot be read as links
+                if let Ok(target) = fs.read_link(&entry.path) {
+                    let resolved_target = fs.canonicalize(&entry.path).ok();
+                    symlinks.push(Symlink {
+                        path: entry.path,
+                        target,
+                        resolved_target,
+                    });
+                }
+            }
+        }
+    }
+    Ok(symlinks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+    use crate::{
+        fs::{
+            dir::DirWalker,
+            vfs::{FilteredFileSystem, RealFileSystem},
+        },
+        path::filter::PathFilter,
+    };
+
+    #[test]
+    fn symlinks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::write(dir.join("a/b/f"), "f").unwrap();
+        symlink(dir.join("a/b"), dir.join("link")).unwrap();
+        symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+        symlink(dir.join("a"), dir.join("a/b/loop")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        let symlinks = find_symlinks(&RealFileSystem, &dir, ErrorBehavior::Stop).unwrap();
+        assert_eq!(symlinks.len(), 3);
+        let link = symlinks
+            .iter()
+            .find(|symlink| symlink.path().ends_with("link"))
+            .unwrap();
+        assert!(link.points_into(&dir.join("a")));
+        assert!(symlinks
+            .iter()
+            .any(|symlink| symlink.path().ends_with("dangling") && symlink.is_dangling()));
+
+        // the linked directory is walked in place of the link, the walk stops where the link loops back
+        let filter = PathFilter::default().with_follow_symlinks(true);
+        let fs = FilteredFileSystem::new(&RealFileSystem, &filter);
+        let mut files = DirWalker::new(&fs, &dir, ErrorBehavior::Stop)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(
+            files,
+            ["a/b/f", "link/f", "link/loop/b/f"].map(|file| dir.join(file))
+        );
+    }
+}
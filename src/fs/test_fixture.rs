@@ -0,0 +1,46 @@
+use std::num::NonZeroUsize;
+
+use crate::{clones::db::ClonesDB, error_behavior::ErrorBehavior, path::HashedAbsolutePath};
+
+use super::{tree::FSTree, vfs::MemoryFileSystem};
+
+/// returns the absolute path of `path` relative to `/fixture`
+pub fn fixture_path(path: impl AsRef<str>) -> HashedAbsolutePath {
+    HashedAbsolutePath::from(format!("/fixture/{}", path.as_ref()).as_str())
+}
+
+/// memory file system holding the (path relative to `/fixture`, size) files
+pub fn fixture_fs<'a>(files: impl IntoIterator<Item = (&'a str, u64)>) -> MemoryFileSystem {
+    let mut fs = MemoryFileSystem::default();
+    for (file, size) in files {
+        fs.insert_file(format!("/fixture/{file}"), size);
+    }
+    fs
+}
+
+/// tree of `/fixture` walked in `fs`
+pub fn fixture_tree(fs: &MemoryFileSystem) -> FSTree {
+    let mut tree = FSTree::default();
+    tree.extend_from_fs_with_progress(
+        "/fixture",
+        fs,
+        ErrorBehavior::Stop,
+        NonZeroUsize::MIN,
+        |_, _| {},
+    )
+    .unwrap();
+    tree
+}
+
+/// clones database of the (file size, paths relative to `/fixture`) clone groups
+pub fn fixture_clones_db<F>(groups: impl IntoIterator<Item = (u64, F)>) -> ClonesDB
+where
+    F: IntoIterator,
+    F::Item: AsRef<str>,
+{
+    ClonesDB::from_clone_groups(
+        groups
+            .into_iter()
+            .map(|(size, files)| (size, files.into_iter().map(fixture_path).collect())),
+    )
+}
@@ -1,11 +1,14 @@
 use std::{
     borrow::{Borrow, Cow},
-    collections::VecDeque,
     error::Error,
-    ffi::{OsStr, OsString},
+    ffi::OsString,
     fmt::Display,
     hash::Hash,
-    path::{self, Path, PathBuf},
+    io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    thread,
 };
 
 use anyhow::anyhow;
@@ -14,13 +17,28 @@ use id_tree::{
     self, InsertBehavior, LevelOrderTraversalIds, Node as IDTreeNode, PreOrderTraversalIds,
 };
 pub use id_tree::{NodeId, NodeIdError};
-use itertools::Itertools;
 use path_absolutize::Absolutize;
+use serde::{Deserialize, Serialize};
 use tap::Tap;
 
-use crate::error_behavior::ErrorBehavior;
+use crate::{
+    error_behavior::ErrorBehavior,
+    path::{filter::PathFilter, PathId},
+};
 
 pub mod clones;
+pub mod coverage;
+pub mod fingerprint;
+pub mod merge_plan;
+pub mod metadata;
+pub mod replication;
+pub mod similarity;
+pub mod snapshot;
+
+use fingerprint::DirFingerprint;
+use metadata::{DirTotals, NodeMetadata};
+
+use super::vfs::{FileSystem, PathMetadata, RealFileSystem};
 
 #[derive(Debug, thiserror::Error)]
 #[error("path `{path}` is not part of node `{node_path}`")]
@@ -37,17 +55,17 @@ pub enum NodePathNodeIdError {
     PathNotFound(#[from] PathNotFound),
 }
 
-#[derive(Debug, Clone, Copy, IsVariant)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IsVariant, Serialize, Deserialize)]
 pub enum PathKind {
     File,
     Directory,
 }
 
 impl PathKind {
-    fn node_data(&self, path: impl Into<PathBuf>) -> NodeData {
+    fn node_data(&self, path_id: PathId) -> NodeData {
         match self {
-            PathKind::File => NodeData::file(path),
-            PathKind::Directory => NodeData::directory(path),
+            PathKind::File => NodeData::file(path_id),
+            PathKind::Directory => NodeData::directory(path_id),
         }
     }
 }
@@ -85,26 +103,43 @@ impl From<FilesIterKind> for InternalFilesIterKind {
 #[derive(Debug, Clone)]
 struct NodeData {
     kind: NodeKind,
-    name: OsString,
-    path: PathBuf,
+    // the path is resolved through the path trie
+    path_id: PathId,
+    // not available for the directories inserted as ancestors of the walked paths
+    metadata: Option<NodeMetadata>,
+    // only available for directories once `FSTree::compute_dir_totals` has been called
+    totals: Option<DirTotals>,
+    // only available for walked directories once `FSTree::compute_dir_fingerprints` has been called
+    fingerprint: Option<DirFingerprint>,
 }
 
 impl NodeData {
-    fn new(path: impl Into<PathBuf>, kind: NodeKind) -> Self {
-        let path = path.into();
-        let name = path
+    fn new(path_id: PathId, kind: NodeKind) -> Self {
+        Self {
+            kind,
+            path_id,
+            metadata: None,
+            totals: None,
+            fingerprint: None,
+        }
+    }
+
+    fn name(&self) -> OsString {
+        self.path_id
             .file_name()
-            .map(OsStr::to_os_string)
-            .unwrap_or_else(|| "/".into());
-        Self { kind, name, path }
+            .unwrap_or_else(|| OsString::from("/"))
     }
 
-    fn directory(path: impl Into<PathBuf>) -> Self {
-        Self::new(path, NodeKind::Directory)
+    fn path(&self) -> PathBuf {
+        self.path_id.to_path_buf()
     }
 
-    fn file(path: impl Into<PathBuf>) -> Self {
-        Self::new(path, NodeKind::File)
+    fn directory(path_id: PathId) -> Self {
+        Self::new(path_id, NodeKind::Directory)
+    }
+
+    fn file(path_id: PathId) -> Self {
+        Self::new(path_id, NodeKind::File)
     }
 
     fn is_directory(&self) -> bool {
@@ -169,15 +204,21 @@ impl<'a> Node<'a> {
         Ok(tree.path_node_id(Self::absolutize_path_impl(node_path, path)?)?)
     }
 
-    pub fn name(&self) -> &'a OsStr {
-        self.node.data().name.as_os_str()
+    pub fn name(&self) -> OsString {
+        self.node.data().name()
     }
-    pub fn path(&self) -> &'a Path {
-        self.node.data().path.as_path()
+    pub fn path(&self) -> PathBuf {
+        self.node.data().path()
     }
     pub fn kind(&self) -> NodeKind {
         self.node.data().kind
     }
+    pub fn path_id(&self) -> PathId {
+        self.node.data().path_id
+    }
+    pub fn metadata(&self) -> Option<NodeMetadata> {
+        self.node.data().metadata
+    }
     pub fn node_id(&self) -> &NodeId {
         &self.node_id
     }
@@ -194,7 +235,7 @@ impl<'a> Node<'a> {
 
     fn check_is_directory(&self) -> Result<(), NodeIsNotADirectory> {
         if !self.node.data().is_directory() {
-            return Err(NodeIsNotADirectory(self.node.data().path.clone()));
+            return Err(NodeIsNotADirectory(self.node.data().path()));
         }
         Ok(())
     }
@@ -289,7 +330,7 @@ impl<'a> Node<'a> {
     // }
 
     pub fn path_node_id(&self, path: &Path) -> Result<&'a NodeId, NodePathNodeIdError> {
-        Node::path_node_id_impl(self.tree, self.path(), path)
+        Node::path_node_id_impl(self.tree, &self.path(), path)
     }
 
     pub fn traverse_nodes(
@@ -507,7 +548,7 @@ impl Hash for Node<'_> {
 
 impl Display for Node<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.node.data().path.to_string_lossy())
+        f.write_str(&self.node.data().path().to_string_lossy())
     }
 }
 
@@ -527,15 +568,27 @@ impl<'a> DirectoryNode<'a> {
         }
     }
 
-    pub fn name(&self) -> &'a OsStr {
-        self.node.data().name.as_os_str()
+    pub fn name(&self) -> OsString {
+        self.node.data().name()
     }
-    pub fn path(&self) -> &'a Path {
-        self.node.data().path.as_path()
+    pub fn path(&self) -> PathBuf {
+        self.node.data().path()
     }
     pub fn kind(&self) -> NodeKind {
         self.node.data().kind
     }
+    pub fn path_id(&self) -> PathId {
+        self.node.data().path_id
+    }
+    pub fn metadata(&self) -> Option<NodeMetadata> {
+        self.node.data().metadata
+    }
+    pub fn totals(&self) -> Option<DirTotals> {
+        self.node.data().totals
+    }
+    pub fn fingerprint(&self) -> Option<DirFingerprint> {
+        self.node.data().fingerprint
+    }
     pub fn node_id(&self) -> &NodeId {
         &self.node_id
     }
@@ -608,7 +661,7 @@ impl<'a> DirectoryNode<'a> {
     // }
 
     pub fn path_node_id(&self, path: &Path) -> Result<&'a NodeId, NodePathNodeIdError> {
-        Node::path_node_id_impl(self.tree, self.path(), path)
+        Node::path_node_id_impl(self.tree, &self.path(), path)
     }
 
     pub fn traverse_nodes(&self, order: TraversalOrder, include_self: bool) -> NodesIter<'a> {
@@ -757,7 +810,7 @@ impl Hash for DirectoryNode<'_> {
 
 impl Display for DirectoryNode<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.node.data().path.to_string_lossy())
+        f.write_str(&self.node.data().path().to_string_lossy())
     }
 }
 
@@ -777,15 +830,24 @@ impl<'a> FileNode<'a> {
         }
     }
 
-    pub fn name(&self) -> &'a OsStr {
-        self.node.data().name.as_os_str()
+    pub fn name(&self) -> OsString {
+        self.node.data().name()
     }
-    pub fn path(&self) -> &'a Path {
-        self.node.data().path.as_path()
+    pub fn path(&self) -> PathBuf {
+        self.node.data().path()
     }
     pub fn kind(&self) -> NodeKind {
         self.node.data().kind
     }
+    pub fn path_id(&self) -> PathId {
+        self.node.data().path_id
+    }
+    pub fn metadata(&self) -> Option<NodeMetadata> {
+        self.node.data().metadata
+    }
+    pub fn size(&self) -> Option<u64> {
+        Some(self.metadata()?.size())
+    }
     pub fn node_id(&self) -> &NodeId {
         &self.node_id
     }
@@ -811,7 +873,7 @@ impl Hash for FileNode<'_> {
 
 impl Display for FileNode<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.node.data().path.to_string_lossy())
+        f.write_str(&self.node.data().path().to_string_lossy())
     }
 }
 
@@ -876,11 +938,24 @@ impl FSTree {
         self.0.insert(IDTreeNode::new(node), behavior)
     }
 
+    fn set_node_metadata(&mut self, node_id: &NodeId, metadata: NodeMetadata) {
+        self.0.get_mut(node_id).unwrap().data_mut().metadata = Some(metadata);
+    }
+
     fn insert_root_node(&mut self) {
-        self.insert_node(NodeData::directory("/"), InsertBehavior::AsRoot)
+        self.insert_node(NodeData::directory(PathId::ROOT), InsertBehavior::AsRoot)
             .unwrap();
     }
 
+    fn insert_child_path_id(
+        &mut self,
+        node_id: &NodeId,
+        path_id: PathId,
+        kind: PathKind,
+    ) -> Result<NodeId, NodeIdError> {
+        self.insert_node(kind.node_data(path_id), InsertBehavior::UnderNode(node_id))
+    }
+
     /// insert child node without checking the node is a directory and not a file which cannot have children
     /// and not checking whether `path` is starts with the node path
     pub fn insert_child_unchecked_impl(
@@ -889,7 +964,10 @@ impl FSTree {
         path: impl Into<PathBuf>,
         kind: PathKind,
     ) -> Result<NodeId, NodeIdError> {
-        self.insert_node(kind.node_data(path), InsertBehavior::UnderNode(node_id))
+        let path = path.into();
+        // the absolutized paths are normalized
+        let path_id = PathId::intern(path.absolutize().unwrap()).unwrap();
+        self.insert_child_path_id(node_id, path_id, kind)
     }
 
     pub fn insert_child_unchecked(
@@ -911,14 +989,12 @@ impl FSTree {
     ) -> Result<NodeId, InsertChildError> {
         let node_data = self.0.get(node_id)?.data();
         if node_data.is_file() {
-            Err(NodeIsNotADirectory(node_data.path.clone()))?
+            Err(NodeIsNotADirectory(node_data.path()))?
         }
         let path = path.into();
-        if !path.starts_with(&node_data.path) {
-            return Err(InsertChildError::Hierarchy {
-                path,
-                node_path: node_data.path.to_owned(),
-            });
+        let node_path = node_data.path();
+        if !path.starts_with(&node_path) {
+            return Err(InsertChildError::Hierarchy { path, node_path });
         }
         Ok(self.insert_child_unchecked_impl(node_id, path, kind)?)
     }
@@ -959,40 +1035,33 @@ impl FSTree {
         path: impl AsRef<Path>,
         kind: PathKind,
     ) -> Result<NodeId, InsertChildError> {
-        let path = path.as_ref().absolutize().unwrap().to_path_buf();
-        let mut current_path = PathBuf::from("/");
+        // the absolutized paths are normalized
+        let path_ids = PathId::intern(path.as_ref().absolutize().unwrap())
+            .unwrap()
+            .ancestors();
         let mut current_node_id = self.root_node_id().clone();
-        let components = path.components().skip(1).collect_vec();
-        let components_count = components.len();
-        for (index, component) in components.into_iter().enumerate() {
-            let path::Component::Normal(comp_os_str) = component else {
-                unreachable!()
-            };
-            current_path = current_path.join(comp_os_str);
+        let components_count = path_ids.len() - 1;
+        for (index, path_id) in path_ids.into_iter().enumerate().skip(1) {
             let mut children_ids = self.0.children_ids(&current_node_id).unwrap();
             match children_ids
-                .find(|node_id| self.0.get(node_id).unwrap().data().name == comp_os_str)
+                .find(|node_id| self.0.get(node_id).unwrap().data().path_id == path_id)
             {
                 Some(node_id) => {
                     let node_data = self.0.get(node_id)?.data();
                     if node_data.is_file() {
-                        Err(NodeIsNotADirectory(node_data.path.clone()))?
+                        Err(NodeIsNotADirectory(node_data.path()))?
                     }
                     current_node_id = node_id.clone()
                 }
                 None => {
-                    let is_last_component = index == components_count - 1;
+                    let is_last_component = index == components_count;
                     let component_kind = if kind.is_file() && is_last_component {
                         PathKind::File
                     } else {
                         PathKind::Directory
                     };
                     current_node_id = self
-                        .insert_child_unchecked_impl(
-                            &current_node_id,
-                            current_path.clone(),
-                            component_kind,
-                        )
+                        .insert_child_path_id(&current_node_id, path_id, component_kind)
                         .unwrap();
                 }
             }
@@ -1042,27 +1111,25 @@ impl FSTree {
 
     fn path_node_id(&self, path: impl AsRef<Path>) -> Result<&NodeId, PathNotFound> {
         let path = path.as_ref().absolutize().unwrap();
+        let path_ids = PathId::get(&path)
+            .ok_or_else(|| PathNotFound(path.to_path_buf()))?
+            .ancestors();
         let mut current_node_id = self.0.root_node_id().unwrap();
-        for component in path.components().skip(1) {
+        for path_id in path_ids.into_iter().skip(1) {
             current_node_id = self
                 .0
                 .get(current_node_id)
                 .unwrap()
                 .children()
                 .iter()
-                .find(|node_id| {
-                    let path::Component::Normal(comp_os_str) = component else {
-                        unreachable!()
-                    };
-                    self.0.get(node_id).unwrap().data().name == comp_os_str
-                })
+                .find(|node_id| self.0.get(node_id).unwrap().data().path_id == path_id)
                 .ok_or_else(|| PathNotFound(path.to_path_buf()))?;
         }
         Ok(current_node_id)
     }
 
-    pub fn node_path(&self, node_id: &NodeId) -> Result<&Path, NodeIdError> {
-        Ok(self.0.get(node_id)?.data().path.as_path())
+    pub fn node_path(&self, node_id: &NodeId) -> Result<PathBuf, NodeIdError> {
+        Ok(self.0.get(node_id)?.data().path())
     }
 
     pub fn contains_path(&self, path: impl AsRef<Path>) -> bool {
@@ -1122,7 +1189,7 @@ impl FSTree {
     fn check_node_is_directory(&self, node_id: &NodeId) -> Result<(), NodeIsNotADirectory> {
         let node = self.0.get(node_id).unwrap();
         if !node.data().is_directory() {
-            return Err(NodeIsNotADirectory(node.data().path.clone()));
+            return Err(NodeIsNotADirectory(node.data().path()));
         }
         Ok(())
     }
@@ -1237,6 +1304,17 @@ impl FSTree {
         })
     }
 
+    /// returns the files in `dir` recursively if `dir` has been walked, meaning the tree holds all its files,
+    /// which is not the case of the directories only inserted as ancestors of the walked paths
+    pub fn walked_dir_files_iter(&self, dir: impl AsRef<Path>) -> Option<FilesIter> {
+        let node = self.node_with_path(&dir).ok()?;
+        if !node.is_directory() || node.metadata().is_none() {
+            return None;
+        }
+        self.path_files_iter(dir, FilesIterKind::RecursivePreOrder)
+            .ok()
+    }
+
     pub fn path_file_nodes_iter(
         &self,
         path: impl AsRef<Path>,
@@ -1283,86 +1361,148 @@ impl FSTree {
         path: impl AsRef<Path>,
         error_message: &str,
     ) -> anyhow::Result<Option<T>> {
-        use ErrorBehavior::*;
-        match value {
-            Ok(value) => Ok(Some(value)),
-            Err(e) => match error_behavior {
-                Ignore => Ok(None),
-                Display | Stop => {
-                    let error_string =
-                        format!("{error_message} `{}`: {e}", path.as_ref().to_string_lossy());
-                    match error_behavior {
-                        Display => {
-                            eprintln!("{error_string}");
-                            Ok(None)
-                        }
-                        Stop => Err(anyhow!("{error_string}")),
-                        _ => unreachable!(),
-                    }
+        error_behavior.handle(value, path, error_message)
+    }
+
+    /// inserts the entries of a directory listed by a walker thread under its node
+    /// calls progress for each dir and file inserted and returns the directories which still need to be listed
+    fn insert_dir_listing(
+        &mut self,
+        dir_node_id: &NodeId,
+        dir: &Path,
+        listing: DirListing,
+        error_behavior: ErrorBehavior,
+        counts: &mut (u64, u64),
+        progress: &mut impl FnMut(u64, u64),
+    ) -> anyhow::Result<Vec<(NodeId, PathBuf)>> {
+        let entries = match listing {
+            DirListing::Entries(entries) => entries,
+            DirListing::ReadDirFailed(error) => {
+                Self::handle_extend_with_dir_error(
+                    Err::<(), _>(error),
+                    error_behavior,
+                    dir,
+                    "failed to read dir",
+                )?;
+                return Ok(vec![]);
+            }
+            DirListing::EntryFailed(error) => {
+                return Err(anyhow!(
+                    "error while extending with dir `{}`: {error}",
+                    dir.to_string_lossy()
+                ))
+            }
+        };
+        let mut child_dirs = vec![];
+        for entry in entries {
+            let Some(kind_and_metadata) = Self::handle_extend_with_dir_error(
+                entry.metadata,
+                error_behavior,
+                &entry.path,
+                "failed to get metadata of file",
+            )?
+            else {
+                continue;
+            };
+            // neither a regular file nor a directory
+            let Some((kind, metadata)) = kind_and_metadata else {
+                continue;
+            };
+            let node_id = self
+                .insert_child_unchecked_impl(dir_node_id, entry.abs_path, kind)
+                .unwrap();
+            self.set_node_metadata(&node_id, metadata);
+            match kind {
+                PathKind::File => counts.1 += 1,
+                PathKind::Directory => {
+                    child_dirs.push((node_id, entry.path));
+                    counts.0 += 1;
                 }
-            },
+            }
+            progress(counts.0, counts.1);
         }
+        Ok(child_dirs)
     }
 
     /// extends the tree with the files in the specified directory, returns an error if the path isn't a directory or does not exist
+    /// directories are listed by `threads` walker threads while the tree is built on the calling thread
     /// calls progress for each dir and file found with the total number of directories and total number of files found
     /// returns the total number of directories and total number of files found
     pub fn extend_with_dir_with_progress(
         &mut self,
         dir: impl Into<PathBuf>,
         error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
+        progress: impl FnMut(u64, u64),
+    ) -> anyhow::Result<(u64, u64)> {
+        self.extend_with_dir_impl(dir, &RealFileSystem, error_behavior, threads, progress)
+    }
+
+    /// same as `extend_with_dir_with_progress` but the directories are listed from `fs`
+    fn extend_with_dir_impl(
+        &mut self,
+        dir: impl Into<PathBuf>,
+        fs: &impl FileSystem,
+        error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
         mut progress: impl FnMut(u64, u64),
     ) -> anyhow::Result<(u64, u64)> {
         let dir: PathBuf = dir.into();
-        if !dir.is_dir() {
-            return Err(anyhow!("not a directory: {}", dir.to_string_lossy()));
-        }
+        let metadata = fs
+            .metadata(&dir)
+            .ok()
+            .filter(PathMetadata::is_dir)
+            .ok_or_else(|| anyhow!("not a directory: {}", dir.to_string_lossy()))?;
         let dir_node_id = self.insert_path_impl(&dir, PathKind::Directory)?;
-        let mut dirs_to_process = VecDeque::from_iter([(dir_node_id, dir)]);
-        let (mut dir_count, mut file_count): (u64, u64) = (1, 0);
-        while let Some((dir_node_id, dir)) = dirs_to_process.pop_back() {
-            let read_dir_result = std::fs::read_dir(&dir);
-            let Some(dir_iter) = Self::handle_extend_with_dir_error(
-                read_dir_result,
-                error_behavior,
-                &dir,
-                "failed to read dir",
-            )?
-            else {
-                continue;
-            };
-            for entry in dir_iter {
-                let entry = entry.map_err(|e| {
-                    anyhow!(
-                        "error while extending with dir `{}`: {e}",
-                        dir.to_string_lossy()
-                    )
-                })?;
-                let Some(file_type) = Self::handle_extend_with_dir_error(
-                    entry.file_type(),
+        self.set_node_metadata(&dir_node_id, metadata.metadata());
+        let mut counts: (u64, u64) = (1, 0);
+        let (job_sender, job_receiver) = mpsc::channel::<(NodeId, PathBuf)>();
+        let (listing_sender, listing_receiver) = mpsc::channel();
+        let job_receiver = Mutex::new(job_receiver);
+        thread::scope(|scope| -> anyhow::Result<()> {
+            // moved into the scope so that they get dropped on early return, which stops the walker threads
+            let (job_sender, listing_receiver) = (job_sender, listing_receiver);
+            for _ in 0..threads.get() {
+                let job_receiver = &job_receiver;
+                let listing_sender = listing_sender.clone();
+                scope.spawn(move || loop {
+                    // the lock is released as soon as a job has been received
+                    let job = job_receiver.lock().unwrap().recv();
+                    // the sender is dropped once the walk is done or has been interrupted by an error
+                    let Ok((dir_node_id, dir)) = job else {
+                        break;
+                    };
+                    let listing = DirListing::read(fs, &dir);
+                    if listing_sender.send((dir_node_id, dir, listing)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(listing_sender);
+
+            job_sender.send((dir_node_id, dir)).unwrap();
+            let mut pending_dirs = 1;
+            while pending_dirs > 0 {
+                let (dir_node_id, dir, listing) = listing_receiver
+                    .recv()
+                    .expect("walker threads should not exit while dirs are pending");
+                pending_dirs -= 1;
+                let child_dirs = self.insert_dir_listing(
+                    &dir_node_id,
+                    &dir,
+                    listing,
                     error_behavior,
-                    entry.path(),
-                    "failed to get type of file",
-                )?
-                else {
-                    continue;
-                };
-                if file_type.is_file() {
-                    let abs_path = entry.path().absolutize().unwrap().to_path_buf();
-                    self.insert_child_unchecked_impl(&dir_node_id, abs_path, PathKind::File)
-                        .unwrap();
-                    file_count += 1;
-                } else if file_type.is_dir() {
-                    let abs_path = entry.path().absolutize().unwrap().to_path_buf();
-                    let child_dir_node_id = self
-                        .insert_child_unchecked_impl(&dir_node_id, abs_path, PathKind::Directory)
-                        .unwrap();
-                    dirs_to_process.push_front((child_dir_node_id, entry.path()));
-                    dir_count += 1;
+                    &mut counts,
+                    &mut progress,
+                )?;
+                pending_dirs += child_dirs.len();
+                for child_dir in child_dirs {
+                    job_sender.send(child_dir).unwrap();
                 }
-                progress(dir_count, file_count);
             }
-        }
+            Ok(())
+        })?;
+        let (dir_count, file_count) = counts;
         progress(dir_count, file_count);
         Ok((dir_count, file_count))
     }
@@ -1371,8 +1511,9 @@ impl FSTree {
         &mut self,
         dir: impl Into<PathBuf>,
         error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
     ) -> anyhow::Result<(u64, u64)> {
-        self.extend_with_dir_with_progress(dir, error_behavior, |_, _| {})
+        self.extend_with_dir_with_progress(dir, error_behavior, threads, |_, _| {})
     }
 
     /// extends the tree with the path if it is a file or all the files in path recursively if path is a directory
@@ -1383,20 +1524,39 @@ impl FSTree {
         &mut self,
         path: impl AsRef<Path>,
         error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
+        progress: impl FnMut(u64, u64),
+    ) -> anyhow::Result<(u64, u64)> {
+        self.extend_from_fs_with_progress(path, &RealFileSystem, error_behavior, threads, progress)
+    }
+
+    /// same as `extend_with_progress` but the paths are listed from `fs` instead of the real file system
+    pub fn extend_from_fs_with_progress(
+        &mut self,
+        path: impl AsRef<Path>,
+        fs: &impl FileSystem,
+        error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
         mut progress: impl FnMut(u64, u64),
     ) -> anyhow::Result<(u64, u64)> {
         let path = path.as_ref();
-        let counts = if path.is_file() {
-            self.insert_file(path)?;
-            progress(0, 1);
-            (0, 1)
-        } else if path.is_dir() {
-            self.extend_with_dir_with_progress(path, error_behavior, progress)?
-        } else {
-            return Err(anyhow!(
-                "not a normal file or directory: {}",
-                path.to_string_lossy()
-            ));
+        let metadata = fs.metadata(path).ok();
+        let counts = match metadata.and_then(|metadata| metadata.kind()) {
+            Some(PathKind::File) => {
+                let file_node_id = self.insert_path_impl(path, PathKind::File)?;
+                self.set_node_metadata(&file_node_id, metadata.unwrap().metadata());
+                progress(0, 1);
+                (0, 1)
+            }
+            Some(PathKind::Directory) => {
+                self.extend_with_dir_impl(path, fs, error_behavior, threads, progress)?
+            }
+            None => {
+                return Err(anyhow!(
+                    "not a normal file or directory: {}",
+                    path.to_string_lossy()
+                ))
+            }
         };
         Ok(counts)
     }
@@ -1405,8 +1565,93 @@ impl FSTree {
         &mut self,
         path: impl AsRef<Path>,
         error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
     ) -> anyhow::Result<(u64, u64)> {
-        self.extend_with_progress(path, error_behavior, |_, _| {})
+        self.extend_with_progress(path, error_behavior, threads, |_, _| {})
+    }
+
+    /// returns a copy of the tree without the entries of the listed directories rejected by `filter`,
+    /// the walked paths themselves are kept
+    pub fn filtered(&self, filter: &PathFilter) -> Self {
+        let mut tree = Self::default();
+        if let Some(metadata) = self.root_node().metadata() {
+            let root_node_id = tree.root_node_id().clone();
+            tree.set_node_metadata(&root_node_id, metadata);
+        }
+        let mut nodes_to_copy = vec![(self.root_node_id().clone(), tree.root_node_id().clone())];
+        while let Some((node_id, copy_node_id)) = nodes_to_copy.pop() {
+            let node = self.0.get(&node_id).unwrap();
+            // the children of a listed directory are entries of its listing
+            let listed = node.data().metadata.is_some();
+            for child_node_id in node.children() {
+                let child_data = self.0.get(child_node_id).unwrap().data();
+                let kind = if child_data.is_file() {
+                    PathKind::File
+                } else {
+                    PathKind::Directory
+                };
+                let child_path = child_data.path();
+                let accepted = match kind {
+                    PathKind::File => match &child_data.metadata {
+                        Some(metadata) => filter.accepts_file_metadata(&child_path, metadata),
+                        None => filter.accepts_file(&child_path),
+                    },
+                    PathKind::Directory => match &child_data.metadata {
+                        Some(metadata) => filter.accepts_dir_metadata(&child_path, metadata),
+                        None => filter.accepts_dir(&child_path),
+                    },
+                };
+                if listed && !accepted {
+                    continue;
+                }
+                let child_copy_node_id = tree
+                    .insert_child_path_id(&copy_node_id, child_data.path_id, kind)
+                    .unwrap();
+                if let Some(metadata) = child_data.metadata {
+                    tree.set_node_metadata(&child_copy_node_id, metadata);
+                }
+                nodes_to_copy.push((child_node_id.clone(), child_copy_node_id));
+            }
+        }
+        tree
+    }
+}
+
+struct DirListingEntry {
+    path: PathBuf,
+    abs_path: PathBuf,
+    // None for entries which are neither regular files nor directories
+    metadata: io::Result<Option<(PathKind, NodeMetadata)>>,
+}
+
+/// result of listing a directory on a walker thread
+enum DirListing {
+    ReadDirFailed(io::Error),
+    EntryFailed(io::Error),
+    Entries(Vec<DirListingEntry>),
+}
+
+impl DirListing {
+    fn read(fs: &impl FileSystem, dir: &Path) -> Self {
+        let dir_entries = match fs.read_dir(dir) {
+            Ok(dir_entries) => dir_entries,
+            Err(error) => return Self::ReadDirFailed(error),
+        };
+        let mut entries = vec![];
+        for entry in dir_entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => return Self::EntryFailed(error),
+            };
+            entries.push(DirListingEntry {
+                abs_path: entry.path.absolutize().unwrap().to_path_buf(),
+                path: entry.path,
+                metadata: entry
+                    .metadata
+                    .map(|metadata| Some((metadata.kind()?, metadata.metadata()))),
+            });
+        }
+        Self::Entries(entries)
     }
 }
 
@@ -1636,15 +1881,12 @@ impl<'a> DirectoriesIter<'a> {
 }
 
 impl<'a> Iterator for DirectoriesIter<'a> {
-    type Item = &'a Path;
+    type Item = PathBuf;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.find_map(|node_id| {
             let node_data = self.tree.0.get(node_id.borrow()).unwrap().data();
-            node_data
-                .kind
-                .is_directory()
-                .then_some(node_data.path.as_path())
+            node_data.kind.is_directory().then(|| node_data.path())
         })
     }
 }
@@ -1676,12 +1918,12 @@ impl<'a> FilesIter<'a> {
 }
 
 impl<'a> Iterator for FilesIter<'a> {
-    type Item = &'a Path;
+    type Item = PathBuf;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.find_map(|node_id| {
             let node_data = self.tree.0.get(node_id.borrow()).unwrap().data();
-            node_data.kind.is_file().then_some(node_data.path.as_path())
+            node_data.kind.is_file().then(|| node_data.path())
         })
     }
 }
@@ -1,8 +1,8 @@
 use std::{
-    collections::{btree_set, hash_map, HashMap},
+    collections::{btree_map, BTreeMap, HashSet},
     fmt::Display,
     hash::Hash,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use anyhow::anyhow;
@@ -12,18 +12,19 @@ use itertools::Itertools;
 use size::Size;
 
 use crate::{
-    clones::db::{CloneRefGroup, ClonesDB},
+    clones::db::{CloneGroup, ClonesDB, FileId},
+    error_behavior::ErrorBehavior,
     fs::{dir, tree::TraversalOrder},
-    path::{HashedAbsolutePath, HashedAbsolutePathRef},
-    paths::{PathRefSet, PathRefs, PathSet, Paths},
+    path::{HashedAbsolutePath, PathId},
+    paths::{PathSet, Paths},
 };
 
-use super::{DirectoryNode, FSTree, FilesIter, FilesIterKind, UpgradedNode};
+use super::{DirectoryNode, FSTree, FileNode, FileNodesIter, FilesIterKind, UpgradedNode};
 
 impl FSTree {
     pub fn unique_files_iter<'a>(&'a self, clones_db: &'a ClonesDB) -> UniqueFilesIter<'a> {
         UniqueFilesIter {
-            files_iter: self.traverse_files(TraversalOrder::Pre),
+            file_nodes_iter: self.traverse_file_nodes(TraversalOrder::Pre),
             clones_db,
         }
     }
@@ -31,7 +32,7 @@ impl FSTree {
     // get the deepest path inside a clone dir which is also a clone dir because it meets the following conditions:
     // - the dir only contains no files (direct children)
     // - the dir only contains one dir
-    fn clone_dir_deep_path<'a>(&'a self, clone_dir_node: &DirectoryNode) -> Option<&'a Path> {
+    fn clone_dir_deep_path(&self, clone_dir_node: &DirectoryNode) -> Option<PathBuf> {
         let mut clone_dir_node = self.0.get(clone_dir_node.node_id()).unwrap();
         let mut first = true;
         loop {
@@ -57,15 +58,19 @@ impl FSTree {
             return None;
         }
 
-        Some(&clone_dir_node.data().path)
+        Some(clone_dir_node.data().path())
     }
 
-    pub fn clone_dirs<'a>(
-        &'a self,
+    /// returns the directories whose files all have clones outside of them, with `against` the clones need to be
+    /// inside one of the given reference roots, the clones inside the `scope` directories do not count
+    pub fn clone_dirs(
+        &self,
         dir: impl AsRef<Path>,
-        clones_db: &'a ClonesDB,
+        clones_db: &ClonesDB,
         recursive: bool,
-    ) -> anyhow::Result<Vec<CloneDir<'a>>> {
+        against: Option<&[HashedAbsolutePath]>,
+        scope: &[HashedAbsolutePath],
+    ) -> anyhow::Result<Vec<CloneDir>> {
         let node = self.node_with_path(&dir)?;
         let UpgradedNode::DirectoryNode(dir_node) = node.upgrade() else {
             return Err(anyhow!(
@@ -84,24 +89,26 @@ impl FSTree {
             let file_clones = current_dir
                 .file_nodes_iter(files_iter_kind)
                 .map(|file_node| {
-                    clones_db.clone_group(file_node.path()).and_then(|group| {
-                        let clones = group.filter_out_dir(current_dir.path());
-                        if clones.is_empty() {
-                            None
-                        } else {
-                            Some((file_node.path(), clones))
-                        }
-                    })
+                    let file_id = clones_db.path_id_file_id(file_node.path_id())?;
+                    let mut clones = clones_db
+                        .file_id_clone_group(file_id)
+                        .filter_out_dir(current_dir.path_id(), clones_db)
+                        .filter_out_dirs(scope, clones_db)
+                        .backups(clones_db);
+                    if let Some(roots) = against {
+                        clones = clones.filter_in_dirs(roots, clones_db);
+                    }
+                    (!clones.is_empty()).then_some((file_id, clones))
                 })
-                .collect::<Option<HashMap<_, _>>>();
+                .collect::<Option<BTreeMap<_, _>>>();
 
             if let Some(file_clones) = file_clones {
                 if !file_clones.is_empty() {
                     let clone_dir = CloneDir {
-                        path: current_dir.path(),
+                        path: current_dir.path().to_path_buf(),
+                        path_id: current_dir.path_id(),
                         deep_path: self.clone_dir_deep_path(&current_dir),
                         clones: file_clones,
-                        clones_db,
                     };
                     clone_dirs.push(clone_dir);
                 }
@@ -118,50 +125,72 @@ impl FSTree {
         dir: impl AsRef<Path>,
         recursive: bool,
         clones_db: &ClonesDB,
-    ) -> anyhow::Result<PathRefSet> {
-        let mut present_groups: Vec<&CloneRefGroup> = vec![];
+    ) -> anyhow::Result<PathSet> {
+        let mut present_groups = HashSet::new();
         let files_iter_kind = if recursive {
             FilesIterKind::RecursivePreOrder
         } else {
             FilesIterKind::Children
         };
         let files = self
-            .path_files_iter(dir, files_iter_kind)?
-            .filter_map(|file| {
-                let file = HashedAbsolutePathRef::new(file).expect("path should be absolute");
-                if present_groups.iter().any(|group| group.contains(&file)) {
-                    None
-                } else {
-                    if let Some(file_group) = clones_db.clone_group(&file) {
-                        present_groups.push(file_group);
-                    }
-                    Some(file.inner())
-                }
-            })
+            .path_file_nodes_iter(dir, files_iter_kind)?
+            .filter_map(
+                |file_node| match clones_db.path_id_file_id(file_node.path_id()) {
+                    Some(file_id) => present_groups
+                        .insert(clones_db.file(file_id).group_id())
+                        .then_some(file_node.path()),
+                    None => Some(file_node.path()),
+                },
+            )
             .collect();
         Ok(files)
     }
 
+    /// returns the files preventing `dir` from being a clone dir: one file of each content found inside `dir` only
+    pub fn dir_blocking_files(
+        &self,
+        dir: impl AsRef<Path>,
+        clones_db: &ClonesDB,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        let dir_id = self.node_with_path(&dir)?.path_id();
+        let blocking_files = self
+            .dir_decloned_inside_files(&dir, true, clones_db)?
+            .iter()
+            .filter(|file| match clones_db.clone_group(file) {
+                Some(clone_group) => clone_group
+                    .filter_out_dir(dir_id, clones_db)
+                    .backups(clones_db)
+                    .is_empty(),
+                None => true,
+            })
+            .map(|file| file.to_path_buf())
+            .sorted_unstable()
+            .collect();
+        Ok(blocking_files)
+    }
+
     // returns groups of identical clone dirs
-    pub fn clone_dir_groups<'a>(
-        &'a self,
+    pub fn clone_dir_groups(
+        &self,
         dir: impl AsRef<Path>,
-        clones_db: &'a ClonesDB,
+        clones_db: &ClonesDB,
         recursive: bool,
-    ) -> anyhow::Result<Vec<CloneDirGroup<'a>>> {
-        let clone_dirs = self.clone_dirs(dir, clones_db, recursive)?;
-        let mut selected = HashMap::new();
+        against: Option<&[HashedAbsolutePath]>,
+        scope: &[HashedAbsolutePath],
+    ) -> anyhow::Result<Vec<CloneDirGroup>> {
+        let clone_dirs = self.clone_dirs(dir, clones_db, recursive, against, scope)?;
+        let mut selected = HashSet::new();
         let clone_dir_groups = clone_dirs
             .iter()
             .filter_map(|clone_dir| {
-                if selected.contains_key(clone_dir.path) {
+                if selected.contains(&clone_dir.path_id) {
                     return None;
                 }
 
                 let clone_dir_group = clone_dirs
                     .iter()
                     .filter(|i_clone_dir| {
-                        if selected.contains_key(i_clone_dir.path) {
+                        if selected.contains(&i_clone_dir.path_id) {
                             return false;
                         }
 
@@ -169,14 +198,10 @@ impl FSTree {
                             return true;
                         }
 
-                        let i_clone_dir_path = HashedAbsolutePath::from(i_clone_dir.path);
-                        let has_missing_files = clone_dir.files_iter().any(|file| match clones_db
-                            .clone_group(file)
-                        {
-                            Some(group) => !group.iter().any(|group_file| {
-                                group_file.starts_with_hashed_path(&i_clone_dir_path)
-                            }),
-                            None => true,
+                        let has_missing_files = clone_dir.files_iter().any(|file| {
+                            !clones_db
+                                .file_id_clone_group(file)
+                                .has_file_in_dir(i_clone_dir.path_id, clones_db)
                         });
 
                         if has_missing_files {
@@ -185,9 +210,17 @@ impl FSTree {
                             let clone_dir_files = clone_dir
                                 .clones
                                 .values()
-                                .flat_map(|crg| crg.iter().map(|path| path.inner().to_owned()))
+                                .flat_map(|group| {
+                                    group.paths(clones_db).map(|path| path.to_path_buf())
+                                })
+                                .collect::<PathSet>();
+                            let i_clone_dir_files = self
+                                .path_files_iter(
+                                    &i_clone_dir.path,
+                                    FilesIterKind::RecursivePreOrder,
+                                )
+                                .unwrap()
                                 .collect::<PathSet>();
-                            let i_clone_dir_files = dir::files_rec(i_clone_dir.path).into_set();
                             let has_extra_files = i_clone_dir_files
                                 .difference(&clone_dir_files)
                                 .next()
@@ -199,7 +232,7 @@ impl FSTree {
                     .collect_vec();
 
                 for clone_dir in &clone_dir_group {
-                    selected.insert(clone_dir.path, ());
+                    selected.insert(clone_dir.path_id);
                 }
 
                 (!clone_dir_group.is_empty()).then_some(CloneDirGroup(clone_dir_group))
@@ -209,50 +242,57 @@ impl FSTree {
     }
 }
 
+/// directory only containing files which have clones outside of it
+/// `clones` maps each file of the directory to its clones outside of the directory
 #[derive(Debug, Getters, CopyGetters, Clone)]
-pub struct CloneDir<'a> {
-    // #[getset(skip)]
-    // scanned_dir_files: &'a DirFilesDB,
-    clones_db: &'a ClonesDB,
+pub struct CloneDir {
+    #[getset(get = "pub")]
+    path: PathBuf,
     #[getset(get_copy = "pub")]
-    path: &'a Path,
+    path_id: PathId,
     #[getset(get = "pub")]
-    deep_path: Option<&'a Path>,
+    deep_path: Option<PathBuf>,
     #[getset(get = "pub")]
-    clones: HashMap<&'a Path, CloneRefGroup<'a>>,
+    clones: BTreeMap<FileId, CloneGroup>,
 }
 
-impl PartialEq for CloneDir<'_> {
+impl PartialEq for CloneDir {
     fn eq(&self, other: &Self) -> bool {
-        self.path == other.path
+        self.path_id == other.path_id
     }
 }
 
-impl Eq for CloneDir<'_> {}
+impl Eq for CloneDir {}
 
-impl Hash for CloneDir<'_> {
+impl Hash for CloneDir {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.path.hash(state);
+        self.path_id.hash(state);
     }
 }
 
-impl Display for CloneDir<'_> {
+impl Display for CloneDir {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&self.path.to_string_lossy())
     }
 }
 
-impl<'a> CloneDir<'a> {
-    pub fn ref_dirs_iter(&self) -> RefDirsIter {
-        let mut clones_iter = self.clones.iter();
-        let first_clone_files_iter = clones_iter.next().unwrap().1.iter();
-        RefDirsIter::new(self, clones_iter, first_clone_files_iter)
+impl CloneDir {
+    pub fn ref_dirs_iter<'a>(&'a self, clones_db: &'a ClonesDB) -> RefDirsIter<'a> {
+        RefDirsIter::new(self, clones_db)
     }
 
     pub fn files_iter(&self) -> CloneDirFilesIter {
         CloneDirFilesIter(self.clones.keys())
     }
 
+    pub fn file_paths<'a>(
+        &'a self,
+        clones_db: &'a ClonesDB,
+    ) -> impl Iterator<Item = &'a HashedAbsolutePath> + 'a {
+        self.files_iter()
+            .map(|file_id| clones_db.file_path(file_id))
+    }
+
     pub fn file_count(&self) -> usize {
         self.clones.len()
     }
@@ -261,16 +301,16 @@ impl<'a> CloneDir<'a> {
         self.clones.values().map(|group| group.file_size()).sum()
     }
 
-    pub fn deep_path_rel(&self) -> Option<&'a Path> {
-        Some(self.deep_path?.strip_prefix(self.path).unwrap())
+    pub fn deep_path_rel(&self) -> Option<&Path> {
+        Some(self.deep_path.as_ref()?.strip_prefix(&self.path).unwrap())
     }
 }
 
 #[derive(Debug, Deref, DerefMut, IntoIterator)]
 #[into_iterator(owned, ref)]
-pub struct CloneDirs<'a>(Vec<CloneDir<'a>>);
+pub struct CloneDirs(Vec<CloneDir>);
 
-impl CloneDirs<'_> {
+impl CloneDirs {
     pub fn file_count(&self) -> usize {
         self.iter().map(CloneDir::file_count).sum()
     }
@@ -284,34 +324,39 @@ impl CloneDirs<'_> {
     }
 }
 
-impl<'a> FromIterator<CloneDir<'a>> for CloneDirs<'a> {
-    fn from_iter<T: IntoIterator<Item = CloneDir<'a>>>(iter: T) -> Self {
+impl FromIterator<CloneDir> for CloneDirs {
+    fn from_iter<T: IntoIterator<Item = CloneDir>>(iter: T) -> Self {
         CloneDirs(Vec::from_iter(iter))
     }
 }
 
-#[derive(Debug, Deref, IntoIterator)]
+#[derive(Debug, Clone, Deref, IntoIterator)]
 #[into_iterator(owned, ref)]
-pub struct CloneDirGroup<'a>(Vec<CloneDir<'a>>);
+pub struct CloneDirGroup(Vec<CloneDir>);
 
-impl CloneDirGroup<'_> {
-    pub fn ref_dirs(&self) -> Vec<RefDir> {
+impl CloneDirGroup {
+    pub fn ref_dirs<'a>(&'a self, clones_db: &ClonesDB) -> Vec<RefDir<'a>> {
         if self.0.is_empty() {
             return vec![];
         }
         let first_clone_dir = self.0.first().unwrap();
         first_clone_dir
             .clones
-            .iter()
-            .flat_map(|(_, clone_group)| {
-                clone_group.files().iter().filter_map(|file| {
+            .values()
+            .flat_map(|clone_group| {
+                clone_group.paths(clones_db).filter_map(|file| {
                     let part_of_clone_dir_group =
-                        self.0.iter().any(|cd| file.inner().starts_with(cd.path));
-                    (!part_of_clone_dir_group).then(|| file.parent().unwrap().to_path_buf())
+                        self.0.iter().any(|cd| file.starts_with_id(cd.path_id));
+                    (!part_of_clone_dir_group).then(|| file.parent_id().unwrap())
                 })
             })
             .unique()
-            .map(|path| RefDir::new(first_clone_dir, HashedAbsolutePath::from(path)))
+            .map(|path_id| {
+                RefDir::new(
+                    first_clone_dir,
+                    HashedAbsolutePath::from(path_id.to_path_buf()),
+                )
+            })
             .collect()
     }
 
@@ -332,10 +377,10 @@ impl CloneDirGroup<'_> {
     }
 }
 
-#[derive(Debug, Deref, IntoIterator)]
-pub struct CloneDirGroups<'a>(Vec<CloneDirGroup<'a>>);
+#[derive(Debug, Clone, Deref, IntoIterator)]
+pub struct CloneDirGroups(Vec<CloneDirGroup>);
 
-impl CloneDirGroups<'_> {
+impl CloneDirGroups {
     pub fn dir_count(&self) -> usize {
         self.iter().map(|group| group.len()).sum()
     }
@@ -363,30 +408,28 @@ impl CloneDirGroups<'_> {
     }
 }
 
-impl<'a> FromIterator<CloneDirGroup<'a>> for CloneDirGroups<'a> {
-    fn from_iter<T: IntoIterator<Item = CloneDirGroup<'a>>>(iter: T) -> Self {
+impl FromIterator<CloneDirGroup> for CloneDirGroups {
+    fn from_iter<T: IntoIterator<Item = CloneDirGroup>>(iter: T) -> Self {
         Self(iter.into_iter().collect())
     }
 }
 
 pub struct RefDirsIter<'a> {
-    clone_dir: &'a CloneDir<'a>,
-    clones_iter: hash_map::Iter<'a, &'a Path, CloneRefGroup<'a>>,
-    clone_files_iter: btree_set::Iter<'a, HashedAbsolutePathRef<'a>>,
-    returned: HashMap<&'a Path, ()>,
+    clone_dir: &'a CloneDir,
+    clones_db: &'a ClonesDB,
+    clones_iter: btree_map::Values<'a, FileId, CloneGroup>,
+    clone_files_iter: std::slice::Iter<'a, FileId>,
+    returned: HashSet<PathId>,
 }
 
 impl<'a> RefDirsIter<'a> {
-    pub fn new(
-        clone_dir: &'a CloneDir<'a>,
-        clones_iter: hash_map::Iter<'a, &'a Path, CloneRefGroup<'a>>,
-        clone_files_iter: btree_set::Iter<'a, HashedAbsolutePathRef<'a>>,
-    ) -> Self {
+    pub fn new(clone_dir: &'a CloneDir, clones_db: &'a ClonesDB) -> Self {
         Self {
             clone_dir,
-            clones_iter,
-            clone_files_iter,
-            returned: HashMap::new(),
+            clones_db,
+            clones_iter: clone_dir.clones.values(),
+            clone_files_iter: [].iter(),
+            returned: HashSet::new(),
         }
     }
 }
@@ -396,13 +439,12 @@ impl<'a> Iterator for RefDirsIter<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            if let Some(clone_file) = self.clone_files_iter.next() {
+            if let Some(&clone_file) = self.clone_files_iter.next() {
                 // file remains in clone_files_iter, get the parent directory
-                let clone_file_dir = clone_file.parent().unwrap();
-                // if this path has not been returned yet, insert it in the `returned` HashMap and return it
-                if let hash_map::Entry::Vacant(entry) = self.returned.entry(clone_file_dir) {
-                    entry.insert(());
-                    let clone_file_dir = HashedAbsolutePath::from(clone_file_dir);
+                let clone_file_dir = self.clones_db.file_path(clone_file).parent_id().unwrap();
+                // if this path has not been returned yet, insert it in the `returned` set and return it
+                if self.returned.insert(clone_file_dir) {
+                    let clone_file_dir = HashedAbsolutePath::from(clone_file_dir.to_path_buf());
                     return Some(RefDir::new(self.clone_dir, clone_file_dir));
                 }
                 // this path has been returned already, start over with the next file in clone_files_iter
@@ -410,7 +452,7 @@ impl<'a> Iterator for RefDirsIter<'a> {
             }
 
             // clone_files_iter has been fully consumed, replace it with the iterator over the next clone group if there is one
-            if let Some((_file, clones)) = self.clones_iter.next() {
+            if let Some(clones) = self.clones_iter.next() {
                 self.clone_files_iter = clones.iter();
             } else {
                 return None;
@@ -421,7 +463,7 @@ impl<'a> Iterator for RefDirsIter<'a> {
 
 #[derive(Debug, Clone, Deref, Getters)]
 pub struct RefDir<'a> {
-    clone_dir: &'a CloneDir<'a>,
+    clone_dir: &'a CloneDir,
     #[deref]
     #[getset(get = "pub")]
     path: HashedAbsolutePath,
@@ -429,91 +471,105 @@ pub struct RefDir<'a> {
 
 impl Display for RefDir<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.path.to_string_lossy())
+        write!(f, "{}", self.path)
     }
 }
 
 impl<'a> RefDir<'a> {
-    pub fn new(clone_dir: &'a CloneDir<'a>, path: HashedAbsolutePath) -> Self {
+    pub fn new(clone_dir: &'a CloneDir, path: HashedAbsolutePath) -> Self {
         Self { clone_dir, path }
     }
 
     /// returns the list of files in the clone dir which match files in this RefDir
-    pub fn clone_dir_clone_files(&self) -> PathRefs {
+    pub fn clone_dir_clone_files(&self, clones_db: &ClonesDB) -> Vec<FileId> {
         self.clone_dir
             .clones
             .iter()
-            .filter_map(|(file, clones)| {
+            .filter_map(|(&file, clones)| {
                 clones
-                    .iter()
-                    .any(|clone| clone.parent_is_hap(&self.path))
-                    .then_some(*file)
+                    .paths(clones_db)
+                    .any(|clone| clone.parent_is_hap(self.path))
+                    .then_some(file)
             })
-            .collect::<PathRefs>()
+            .collect()
     }
 
     /// returns files which are in the clone dir but not in this RefDir
-    pub fn missing(&self) -> PathRefs {
+    pub fn missing(&self, clones_db: &ClonesDB) -> Vec<FileId> {
         self.clone_dir
             .files_iter()
-            .filter(|file| match self.clone_dir.clones_db.clone_group(file) {
-                Some(group) => !group
-                    .iter()
-                    .any(|group_file| group_file.starts_with_hashed_path(&self.path)),
-                None => true,
+            .filter(|&file| {
+                !clones_db
+                    .file_id_clone_group(file)
+                    .has_file_in_dir(self.path.id(), clones_db)
             })
             .collect()
     }
 
-    pub fn clone_files(&self) -> PathRefs {
+    pub fn clone_files(&self, clones_db: &ClonesDB) -> Vec<FileId> {
         self.clone_dir
             .clones
             .values()
-            .flat_map(CloneRefGroup::files)
-            .filter_map(|clone| {
-                clone
-                    .parent_is_hap(&self.path)
-                    .then_some(AsRef::<Path>::as_ref(clone))
-            })
+            .flat_map(|clones| clones.iter().copied())
+            .filter(|&clone| clones_db.file_path(clone).parent_is_hap(self.path))
             .collect()
     }
 
     /// returns files which are in this RefDir but not in the clone dir
-    pub fn extra(&self) -> Paths {
-        // FIXME: better than above but we might still be walking the same tree multiple times
-        dir::files_rec(self.path.as_path())
-            .into_set()
-            .difference(&Paths::from(self.clone_files()).into_set())
+    /// the files are taken from `file_tree` when it contains the RefDir, otherwise the RefDir is walked handling the
+    /// listing errors according to `error_behavior`
+    pub fn extra(
+        &self,
+        clones_db: &ClonesDB,
+        file_tree: &FSTree,
+        error_behavior: ErrorBehavior,
+    ) -> anyhow::Result<Paths> {
+        let clone_files = self
+            .clone_files(clones_db)
+            .into_iter()
+            .map(|clone| clones_db.file_path(clone).to_path_buf())
+            .collect::<PathSet>();
+        let ref_dir_files = match file_tree.walked_dir_files_iter(self.path.to_path_buf()) {
+            Some(files) => files.collect::<PathSet>(),
+            None => dir::files_rec(self.path.to_path_buf(), error_behavior)?.into_set(),
+        };
+        Ok(ref_dir_files
+            .difference(&clone_files)
             // don't count the file as extra if the file is in the clone dir which can happen if the clone dir is inside the ref dir
-            .filter(|file| !file.starts_with(self.clone_dir.path))
+            .filter(|file| !file.starts_with(&self.clone_dir.path))
             .cloned()
-            .collect()
+            .collect())
     }
 }
 
-pub struct CloneDirFilesIter<'a>(hash_map::Keys<'a, &'a Path, CloneRefGroup<'a>>);
+pub struct CloneDirFilesIter<'a>(btree_map::Keys<'a, FileId, CloneGroup>);
 
-impl<'a> Iterator for CloneDirFilesIter<'a> {
-    type Item = &'a Path;
+impl Iterator for CloneDirFilesIter<'_> {
+    type Item = FileId;
 
     fn next(&mut self) -> Option<Self::Item> {
-        Some(*self.0.next()?)
+        self.0.next().copied()
     }
 }
 
 pub struct UniqueFilesIter<'a> {
-    files_iter: FilesIter<'a>,
+    file_nodes_iter: FileNodesIter<'a>,
     clones_db: &'a ClonesDB,
 }
 
 impl<'a> Iterator for UniqueFilesIter<'a> {
-    type Item = &'a Path;
+    type Item = FileNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let file = self.files_iter.next()?;
-            if !self.clones_db.file_is_a_clone(file) {
-                return Some(file);
+            let file_node = self.file_nodes_iter.next()?;
+            // a file whose only clones are not-a-backup files is unique
+            if !self
+                .clones_db
+                .path_id_file_id(file_node.path_id())
+                .is_some_and(|file_id| self.clones_db.file_has_backup(file_id))
+            {
+                return Some(file_node);
             }
         }
     }
@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{clones::db::ClonesDB, path::HashedAbsolutePath};
+
+use super::{FSTree, FilesIterKind};
+
+/// file which has no clone under the reference roots
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct UncoveredFile {
+    #[getset(get = "pub")]
+    path: PathBuf,
+    #[getset(get_copy = "pub")]
+    size: u64,
+    /// false if the file is outside of the paths scanned to build the clones list, its clones being unknown
+    #[getset(get_copy = "pub")]
+    scanned: bool,
+}
+
+impl FSTree {
+    /// lists the files of `dir` which have no clone outside of `dir` under one of the `roots`
+    pub fn uncovered_files(
+        &self,
+        dir: impl AsRef<Path>,
+        roots: &[HashedAbsolutePath],
+        clones_db: &ClonesDB,
+    ) -> anyhow::Result<Vec<UncoveredFile>> {
+        let dir_node = self.node_with_path(&dir)?;
+        let dir_id = dir_node.path_id();
+        let uncovered_files = dir_node
+            .file_nodes_iter(FilesIterKind::RecursivePreOrder)?
+            .filter_map(|file_node| {
+                let scanned = clones_db.was_scanned(file_node.path());
+                let covered = scanned
+                    && clones_db
+                        .path_id_clone_group(file_node.path_id())
+                        .is_some_and(|clone_group| {
+                            let outside_clones = clone_group
+                                .filter_out_dir(dir_id, clones_db)
+                                .backups(clones_db);
+                            roots
+                                .iter()
+                                .any(|root| outside_clones.has_file_in_dir(root.id(), clones_db))
+                        });
+                (!covered).then(|| UncoveredFile {
+                    path: file_node.path().to_path_buf(),
+                    size: file_node.size().unwrap_or(0),
+                    scanned,
+                })
+            })
+            .collect();
+        Ok(uncovered_files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use crate::{
+        fs::test_fixture::{fixture_clones_db, fixture_fs, fixture_tree},
+        path::{filter::PathFilter, ignore::IgnoreFiles},
+    };
+
+    use super::*;
+
+    #[test]
+    fn uncovered_files() {
+        let fs = fixture_fs(
+            ["laptop/a", "laptop/b", "laptop/c", "nas/a", "trash/b"].map(|file| (file, 10)),
+        );
+        let tree = fixture_tree(&fs);
+        let clones_db =
+            fixture_clones_db([(10, ["laptop/a", "nas/a"]), (10, ["laptop/b", "trash/b"])]);
+        let uncovered_files = tree
+            .uncovered_files(
+                "/fixture/laptop",
+                &[HashedAbsolutePath::from("/fixture/nas")],
+                &clones_db,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .sorted_unstable()
+            .collect_vec();
+        assert_eq!(
+            uncovered_files,
+            vec![
+                PathBuf::from("/fixture/laptop/b"),
+                PathBuf::from("/fixture/laptop/c")
+            ]
+        );
+    }
+
+    #[test]
+    fn ignored_files_are_uncovered() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let global_ignore_file = temp_dir.path().join("ignore");
+        std::fs::write(&global_ignore_file, "*.tmp\n").unwrap();
+        let filter = PathFilter::default()
+            .with_ignore_files(IgnoreFiles::new(Some(&global_ignore_file)).unwrap());
+        let fs = fixture_fs([("laptop/a", 10), ("laptop/b.tmp", 10), ("nas/a", 10)]);
+        let tree = fixture_tree(&fs);
+        let clones_db = fixture_clones_db([(10, ["laptop/a", "nas/a"])]);
+        let uncovered_files = |tree: &FSTree| {
+            tree.uncovered_files(
+                "/fixture/laptop",
+                &[HashedAbsolutePath::from("/fixture/nas")],
+                &clones_db,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect_vec()
+        };
+        // the ignored file is deleted along with the directory, it must not be left out
+        assert!(uncovered_files(&tree.filtered(&filter)).is_empty());
+        assert_eq!(
+            uncovered_files(&tree.filtered(&filter.without_ignore_files())),
+            vec![PathBuf::from("/fixture/laptop/b.tmp")]
+        );
+    }
+}
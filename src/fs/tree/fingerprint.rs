@@ -0,0 +1,315 @@
+use std::{collections::HashMap, hash::Hash, os::unix::ffi::OsStrExt, path::PathBuf};
+
+use getset::{CopyGetters, Getters};
+use itertools::Itertools;
+use size::Size;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{
+    clones::db::{ClonesDB, GroupId},
+    path::PathId,
+};
+
+use super::{FSTree, NodeId, NodeKind};
+
+/// identity of the content of a directory: two directories with the same fingerprint contain the same files,
+/// files being the same if they are part of the same clone group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct DirFingerprint {
+    #[getset(skip)]
+    hash: u128,
+    /// total size of the files contained in the directory recursively
+    size: u64,
+    /// number of files contained in the directory recursively
+    file_count: u64,
+}
+
+impl DirFingerprint {
+    pub fn size_human(&self) -> Size {
+        Size::from_bytes(self.size)
+    }
+}
+
+/// identity of a directory entry
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum EntryIdentity {
+    /// file which is part of a clone group, identified by its group
+    Clone(GroupId),
+    /// file which has no clone, identified by its path
+    Unique(PathId),
+    Directory(u128),
+}
+
+/// entry of a directory collected while computing the fingerprint of the directory
+struct FingerprintEntry {
+    // empty when the names are ignored
+    name: Vec<u8>,
+    identity: EntryIdentity,
+    size: u64,
+    file_count: u64,
+}
+
+/// directories having the same fingerprint
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct IdenticalDirGroup {
+    #[getset(get_copy = "pub")]
+    fingerprint: DirFingerprint,
+    #[getset(get = "pub")]
+    dirs: Vec<PathBuf>,
+}
+
+impl IdenticalDirGroup {
+    /// size which would be freed by keeping only one of the directories
+    pub fn reclaimable_size(&self) -> u64 {
+        self.fingerprint.size * (self.dirs.len() as u64 - 1)
+    }
+}
+
+impl FSTree {
+    /// computes the fingerprint of every walked directory of the tree in one pass from the clone groups of its files,
+    /// they are then available through `DirectoryNode::fingerprint`
+    /// with `ignore_names` the names of the files and directories are not part of the fingerprint
+    pub fn compute_dir_fingerprints(&mut self, clones_db: &ClonesDB, ignore_names: bool) {
+        let node_ids = self
+            .0
+            .traverse_pre_order_ids(self.root_node_id())
+            .unwrap()
+            .collect_vec();
+        let mut dir_entries: HashMap<NodeId, Vec<FingerprintEntry>> = HashMap::new();
+        // children come after their parent in pre-order so iterating backwards visits a directory after all its children
+        for node_id in node_ids.iter().rev() {
+            let node = self.0.get(node_id).unwrap();
+            let parent_id = node.parent().cloned();
+            let data = node.data();
+            let name = if ignore_names {
+                vec![]
+            } else {
+                data.name().as_bytes().to_vec()
+            };
+            let (identity, size, file_count) = match data.kind {
+                NodeKind::File => {
+                    let size = data.metadata.map_or(0, |metadata| metadata.size());
+                    let identity = match clones_db.path_id_clone_group(data.path_id) {
+                        Some(clone_group) => EntryIdentity::Clone(clone_group.id()),
+                        None => EntryIdentity::Unique(data.path_id),
+                    };
+                    (identity, size, 1)
+                }
+                NodeKind::Directory => {
+                    let mut entries = dir_entries.remove(node_id).unwrap_or_default();
+                    entries.sort_unstable_by(|entry1, entry2| {
+                        (&entry1.name, &entry1.identity).cmp(&(&entry2.name, &entry2.identity))
+                    });
+                    let mut hasher = Xxh3::new();
+                    let (mut size, mut file_count) = (0, 0);
+                    for entry in &entries {
+                        entry.name.hash(&mut hasher);
+                        entry.identity.hash(&mut hasher);
+                        size += entry.size;
+                        file_count += entry.file_count;
+                    }
+                    let fingerprint = DirFingerprint {
+                        hash: hasher.digest128(),
+                        size,
+                        file_count,
+                    };
+                    let node_data = self.0.get_mut(node_id).unwrap().data_mut();
+                    // the directories only inserted as ancestors of the walked paths do not hold all their files
+                    if node_data.metadata.is_some() {
+                        node_data.fingerprint = Some(fingerprint);
+                    }
+                    (EntryIdentity::Directory(fingerprint.hash), size, file_count)
+                }
+            };
+            if let Some(parent_id) = parent_id {
+                dir_entries
+                    .entry(parent_id)
+                    .or_default()
+                    .push(FingerprintEntry {
+                        name,
+                        identity,
+                        size,
+                        file_count,
+                    });
+            }
+        }
+    }
+
+    /// returns the files of the directory recursively as sorted (relative path, size, identity) entries, the relative paths
+    /// being empty with `ignore_names`
+    fn dir_content(
+        &self,
+        node_id: &NodeId,
+        clones_db: &ClonesDB,
+        ignore_names: bool,
+    ) -> Vec<(Vec<u8>, u64, EntryIdentity)> {
+        let dir_path = self.0.get(node_id).unwrap().data().path();
+        self.0
+            .traverse_pre_order_ids(node_id)
+            .unwrap()
+            .map(|node_id| self.0.get(&node_id).unwrap().data())
+            .filter(|data| data.kind == NodeKind::File)
+            .map(|data| {
+                let name = if ignore_names {
+                    vec![]
+                } else {
+                    let path = data.path();
+                    let relative_path = path.strip_prefix(&dir_path).unwrap();
+                    relative_path.as_os_str().as_bytes().to_vec()
+                };
+                let identity = match clones_db.path_id_clone_group(data.path_id) {
+                    Some(clone_group) => EntryIdentity::Clone(clone_group.id()),
+                    None => EntryIdentity::Unique(data.path_id),
+                };
+                let size = data.metadata.map_or(0, |metadata| metadata.size());
+                (name, size, identity)
+            })
+            .sorted_unstable()
+            .collect()
+    }
+
+    /// groups the non-empty directories which have the same fingerprint, `compute_dir_fingerprints` needs to be called first
+    /// with the same `ignore_names`, the contents of the directories are compared to confirm that they are identical
+    /// the subdirectories of identical directories are not reported unless they are also identical to other directories
+    pub fn identical_dir_groups(
+        &self,
+        clones_db: &ClonesDB,
+        ignore_names: bool,
+    ) -> Vec<IdenticalDirGroup> {
+        let mut fingerprint_dirs: HashMap<DirFingerprint, Vec<NodeId>> = HashMap::new();
+        for node_id in self.0.traverse_pre_order_ids(self.root_node_id()).unwrap() {
+            let data = self.0.get(&node_id).unwrap().data();
+            if let Some(fingerprint) = data.fingerprint.filter(|fp| fp.file_count > 0) {
+                fingerprint_dirs
+                    .entry(fingerprint)
+                    .or_default()
+                    .push(node_id);
+            }
+        }
+        let mut groups = fingerprint_dirs
+            .into_iter()
+            .filter(|(_, node_ids)| node_ids.len() > 1)
+            .filter(|(_, node_ids)| !self.parents_are_identical(node_ids))
+            // the directories whose fingerprints only collide are split apart
+            .flat_map(|(fingerprint, node_ids)| {
+                let mut content_node_ids: Vec<(Vec<_>, Vec<NodeId>)> = vec![];
+                for node_id in node_ids {
+                    let content = self.dir_content(&node_id, clones_db, ignore_names);
+                    match content_node_ids
+                        .iter_mut()
+                        .find(|(other_content, _)| *other_content == content)
+                    {
+                        Some((_, node_ids)) => node_ids.push(node_id),
+                        None => content_node_ids.push((content, vec![node_id])),
+                    }
+                }
+                content_node_ids
+                    .into_iter()
+                    .map(move |(_, node_ids)| (fingerprint, node_ids))
+            })
+            .filter(|(_, node_ids)| node_ids.len() > 1)
+            .map(|(fingerprint, node_ids)| IdenticalDirGroup {
+                fingerprint,
+                dirs: node_ids
+                    .into_iter()
+                    .map(|node_id| self.0.get(&node_id).unwrap().data().path())
+                    .sorted_unstable()
+                    .collect(),
+            })
+            .collect_vec();
+        groups.sort_unstable_by(|group1, group2| {
+            group2
+                .fingerprint
+                .size
+                .cmp(&group1.fingerprint.size)
+                .then_with(|| group1.dirs.cmp(&group2.dirs))
+        });
+        groups
+    }
+
+    /// returns true if the directories have distinct parents which all have the same fingerprint,
+    /// in which case the directories are already reported through their parents
+    fn parents_are_identical(&self, node_ids: &[NodeId]) -> bool {
+        let parent_ids = node_ids
+            .iter()
+            .filter_map(|node_id| self.0.get(node_id).unwrap().parent())
+            .collect_vec();
+        if parent_ids.len() != node_ids.len() || !parent_ids.iter().all_unique() {
+            return false;
+        }
+        parent_ids
+            .iter()
+            .map(|parent_id| self.0.get(parent_id).unwrap().data().fingerprint)
+            .all_equal_value()
+            .is_ok_and(|fingerprint| fingerprint.is_some_and(|fp| fp.file_count > 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        clones::db::ClonesDB,
+        fs::test_fixture::{fixture_clones_db, fixture_fs, fixture_tree},
+    };
+
+    use super::*;
+
+    fn tree_and_clones_db() -> (FSTree, ClonesDB) {
+        let fs = fixture_fs([
+            ("x/q/r", 4),
+            ("y/q/r", 4),
+            ("z/q/r", 4),
+            ("x/p", 3),
+            ("y/p", 3),
+            ("z/p2", 3),
+        ]);
+        let clones_db = fixture_clones_db([
+            (4, ["x/q/r", "y/q/r", "z/q/r"]),
+            (3, ["x/p", "y/p", "z/p2"]),
+        ]);
+        (fixture_tree(&fs), clones_db)
+    }
+
+    fn group_dirs(groups: &[IdenticalDirGroup]) -> Vec<Vec<&Path>> {
+        groups
+            .iter()
+            .map(|group| group.dirs().iter().map(PathBuf::as_path).collect())
+            .collect()
+    }
+
+    #[test]
+    fn identical_dirs() {
+        let (mut tree, clones_db) = tree_and_clones_db();
+        tree.compute_dir_fingerprints(&clones_db, false);
+        let groups = tree.identical_dir_groups(&clones_db, false);
+        assert_eq!(
+            group_dirs(&groups),
+            vec![
+                vec![Path::new("/fixture/x"), Path::new("/fixture/y")],
+                vec![
+                    Path::new("/fixture/x/q"),
+                    Path::new("/fixture/y/q"),
+                    Path::new("/fixture/z/q")
+                ],
+            ]
+        );
+        assert_eq!(groups[0].reclaimable_size(), 7);
+    }
+
+    #[test]
+    fn identical_dirs_ignoring_names() {
+        let (mut tree, clones_db) = tree_and_clones_db();
+        tree.compute_dir_fingerprints(&clones_db, true);
+        assert_eq!(
+            group_dirs(&tree.identical_dir_groups(&clones_db, true)),
+            vec![vec![
+                Path::new("/fixture/x"),
+                Path::new("/fixture/y"),
+                Path::new("/fixture/z")
+            ]]
+        );
+    }
+}
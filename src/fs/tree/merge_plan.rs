@@ -0,0 +1,253 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use getset::Getters;
+
+use crate::clones::db::{ClonesDB, GroupId};
+
+use super::{FSTree, FilesIterKind, NodeKind};
+
+/// what to do with a file of the source directory to merge it into the target directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeAction {
+    /// the file already has a copy in the target directory, it can be dropped
+    Drop {
+        file: PathBuf,
+        copy: PathBuf,
+        size: u64,
+    },
+    /// the file has no copy in the target directory, it is copied to the same relative path in the target directory
+    Copy {
+        file: PathBuf,
+        destination: PathBuf,
+        size: u64,
+    },
+    /// the relative path of the file in the target directory is taken by a file with a different content
+    Conflict {
+        file: PathBuf,
+        existing: PathBuf,
+        size: u64,
+    },
+}
+
+impl MergeAction {
+    pub fn file(&self) -> &Path {
+        match self {
+            MergeAction::Drop { file, .. }
+            | MergeAction::Copy { file, .. }
+            | MergeAction::Conflict { file, .. } => file,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        match self {
+            MergeAction::Drop { size, .. }
+            | MergeAction::Copy { size, .. }
+            | MergeAction::Conflict { size, .. } => *size,
+        }
+    }
+}
+
+/// actions turning the source directory into a clone directory of the target directory
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+pub struct MergePlan {
+    source: PathBuf,
+    target: PathBuf,
+    /// one action per file of the source directory, sorted by file path
+    actions: Vec<MergeAction>,
+}
+
+impl MergePlan {
+    /// returns true if the source directory is a clone directory of the target directory once the plan is executed
+    pub fn is_complete(&self) -> bool {
+        !self
+            .actions
+            .iter()
+            .any(|action| matches!(action, MergeAction::Conflict { .. }))
+    }
+
+    /// writes a shell script copying the files to the target directory without overwriting existing files, the
+    /// conflicts are written as comments
+    pub fn write_script(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "#!/bin/sh")?;
+        writeln!(
+            writer,
+            "# merge of {:?} into {:?}",
+            self.source, self.target
+        )?;
+        writeln!(writer, "set -e")?;
+        let mut created_dirs = HashSet::new();
+        for action in &self.actions {
+            match action {
+                MergeAction::Drop { .. } => {}
+                MergeAction::Copy {
+                    file, destination, ..
+                } => {
+                    let dir = destination.parent().unwrap();
+                    if created_dirs.insert(dir) {
+                        writer.write_all(b"mkdir -p ")?;
+                        write_shell_quoted(&mut writer, dir)?;
+                        writeln!(writer)?;
+                    }
+                    writer.write_all(b"cp -n -p ")?;
+                    write_shell_quoted(&mut writer, file)?;
+                    writer.write_all(b" ")?;
+                    write_shell_quoted(&mut writer, destination)?;
+                    writeln!(writer)?;
+                }
+                MergeAction::Conflict { file, existing, .. } => {
+                    writeln!(writer, "# conflict: {file:?} differs from {existing:?}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_shell_quoted(writer: &mut impl Write, path: &Path) -> io::Result<()> {
+    writer.write_all(b"'")?;
+    let parts = path.as_os_str().as_bytes().split(|&byte| byte == b'\'');
+    for (index, part) in parts.enumerate() {
+        if index > 0 {
+            writer.write_all(b"'\\''")?;
+        }
+        writer.write_all(part)?;
+    }
+    writer.write_all(b"'")
+}
+
+impl FSTree {
+    /// plans the merge of the `source` directory into the `target` directory, both need to have been walked:
+    /// the files of `source` having a copy in `target` can be dropped, the others are copied to their relative path
+    /// in `target` unless it is taken by a file with a different content
+    pub fn merge_plan(
+        &self,
+        source: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+        clones_db: &ClonesDB,
+    ) -> anyhow::Result<MergePlan> {
+        let source_node = self.node_with_path(&source)?;
+        let target_node = self.node_with_path(&target)?;
+        let (source, target) = (source_node.path(), target_node.path());
+        if source.starts_with(&target) || target.starts_with(&source) {
+            return Err(anyhow!(
+                "cannot merge nested directories: {} and {}",
+                source.to_string_lossy(),
+                target.to_string_lossy()
+            ));
+        }
+        // copies of the files which have no copy in the target directory yet
+        let mut planned_copies: HashMap<GroupId, PathBuf> = HashMap::new();
+        let mut actions = vec![];
+        for file_node in source_node.file_nodes_iter(FilesIterKind::RecursivePreOrder)? {
+            let file = file_node.path().to_path_buf();
+            let size = file_node.size().unwrap_or(0);
+            let clone_group = clones_db.path_id_clone_group(file_node.path_id());
+            let copy = clone_group.and_then(|clone_group| {
+                clone_group
+                    .paths(clones_db)
+                    .find(|path| path.starts_with_id(target_node.path_id()))
+                    .map(|path| path.to_path_buf())
+                    .or_else(|| planned_copies.get(&clone_group.id()).cloned())
+            });
+            if let Some(copy) = copy {
+                actions.push(MergeAction::Drop { file, copy, size });
+                continue;
+            }
+            let destination = target.join(file.strip_prefix(&source).unwrap());
+            let existing = destination
+                .ancestors()
+                .take_while(|path| *path != target)
+                .filter_map(|path| self.node_with_path(path).ok())
+                .find(|node| node.path() == destination || node.kind() == NodeKind::File);
+            match existing {
+                Some(existing) => actions.push(MergeAction::Conflict {
+                    file,
+                    existing: existing.path().to_path_buf(),
+                    size,
+                }),
+                None => {
+                    if let Some(clone_group) = clone_group {
+                        planned_copies.insert(clone_group.id(), destination.clone());
+                    }
+                    actions.push(MergeAction::Copy {
+                        file,
+                        destination,
+                        size,
+                    });
+                }
+            }
+        }
+        actions.sort_unstable_by(|action1, action2| action1.file().cmp(action2.file()));
+        Ok(MergePlan {
+            source,
+            target,
+            actions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::test_fixture::{fixture_clones_db, fixture_fs, fixture_tree};
+
+    use super::*;
+
+    #[test]
+    fn merge_plan() {
+        let fs = fixture_fs(
+            [
+                "a/same",
+                "a/unique",
+                "a/sub/unique",
+                "a/clash",
+                "b/renamed",
+                "b/clash",
+            ]
+            .map(|file| (file, 10)),
+        );
+        let tree = fixture_tree(&fs);
+        let clones_db = fixture_clones_db([(10, ["a/same", "b/renamed"])]);
+        let plan = tree
+            .merge_plan("/fixture/a", "/fixture/b", &clones_db)
+            .unwrap();
+        let path = |path: &str| PathBuf::from(format!("/fixture/{path}"));
+        assert_eq!(
+            plan.actions(),
+            &vec![
+                MergeAction::Conflict {
+                    file: path("a/clash"),
+                    existing: path("b/clash"),
+                    size: 10
+                },
+                MergeAction::Drop {
+                    file: path("a/same"),
+                    copy: path("b/renamed"),
+                    size: 10
+                },
+                MergeAction::Copy {
+                    file: path("a/sub/unique"),
+                    destination: path("b/sub/unique"),
+                    size: 10
+                },
+                MergeAction::Copy {
+                    file: path("a/unique"),
+                    destination: path("b/unique"),
+                    size: 10
+                },
+            ]
+        );
+        assert!(!plan.is_complete());
+        let mut script = vec![];
+        plan.write_script(&mut script).unwrap();
+        assert!(String::from_utf8(script)
+            .unwrap()
+            .contains("cp -n -p '/fixture/a/sub/unique' '/fixture/b/sub/unique'\n"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::{collections::HashMap, os::unix::prelude::MetadataExt, time::SystemTime};
+
+use derive_more::{Add, AddAssign, Constructor};
+use getset::CopyGetters;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use size::Size;
+
+use crate::clones::db::ClonesDB;
+
+use super::{FSTree, NodeId, NodeKind};
+
+/// metadata recorded for a node while walking the file system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Constructor, CopyGetters, Serialize, Deserialize)]
+#[getset(get_copy = "pub")]
+pub struct NodeMetadata {
+    size: u64,
+    modified: SystemTime,
+    inode: u64,
+    device: u64,
+}
+
+impl From<&std::fs::Metadata> for NodeMetadata {
+    fn from(metadata: &std::fs::Metadata) -> Self {
+        Self {
+            size: metadata.len(),
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            inode: metadata.ino(),
+            device: metadata.dev(),
+        }
+    }
+}
+
+/// totals of the files contained in a directory recursively
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Add, AddAssign, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct DirTotals {
+    size: u64,
+    file_count: u64,
+    /// size of the files which are part of the clones list
+    clone_size: u64,
+    /// size of the files which are not part of the clones list
+    unique_size: u64,
+}
+
+impl DirTotals {
+    fn file(size: u64, is_a_clone: bool) -> Self {
+        Self {
+            size,
+            file_count: 1,
+            clone_size: if is_a_clone { size } else { 0 },
+            unique_size: if is_a_clone { 0 } else { size },
+        }
+    }
+
+    pub fn size_human(&self) -> Size {
+        Size::from_bytes(self.size)
+    }
+}
+
+impl FSTree {
+    /// computes the totals of every directory of the tree in one pass, they are then available through `DirectoryNode::totals`
+    pub fn compute_dir_totals(&mut self, clones_db: &ClonesDB) {
+        let node_ids = self
+            .0
+            .traverse_pre_order_ids(self.root_node_id())
+            .unwrap()
+            .collect_vec();
+        let mut dir_totals: HashMap<NodeId, DirTotals> = HashMap::new();
+        // children come after their parent in pre-order so iterating backwards visits a directory after all its children
+        for node_id in node_ids.iter().rev() {
+            let node = self.0.get(node_id).unwrap();
+            let parent_id = node.parent().cloned();
+            let data = node.data();
+            let totals = match data.kind {
+                NodeKind::File => DirTotals::file(
+                    data.metadata.map_or(0, |metadata| metadata.size()),
+                    clones_db.path_id_file_id(data.path_id).is_some(),
+                ),
+                NodeKind::Directory => {
+                    let totals = dir_totals.remove(node_id).unwrap_or_default();
+                    self.0.get_mut(node_id).unwrap().data_mut().totals = Some(totals);
+                    totals
+                }
+            };
+            if let Some(parent_id) = parent_id {
+                *dir_totals.entry(parent_id).or_default() += totals;
+            }
+        }
+    }
+}
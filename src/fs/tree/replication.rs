@@ -0,0 +1,265 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use getset::{CopyGetters, Getters};
+use itertools::Itertools;
+use size::Size;
+
+use crate::{
+    clones::db::{CloneGroup, ClonesDB, GroupId},
+    fs::vfs::FileSystem,
+};
+
+use super::{FSTree, FileNode, NodeId, TraversalOrder};
+
+/// content which has fewer copies than required
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct AtRiskContent {
+    /// files having the content, a single file for unique files
+    #[getset(get = "pub")]
+    files: Vec<PathBuf>,
+    #[getset(get_copy = "pub")]
+    size: u64,
+    /// number of copies, or of distinct devices holding a copy when counting the devices
+    #[getset(get_copy = "pub")]
+    copies: usize,
+}
+
+impl AtRiskContent {
+    pub fn size_human(&self) -> Size {
+        Size::from_bytes(self.size)
+    }
+}
+
+/// size of the contents at risk found in a top-level directory, the directories directly under the walked paths
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct TopLevelDirRisk {
+    #[getset(get = "pub")]
+    dir: PathBuf,
+    /// each content is counted once even if several of its copies are in the directory
+    #[getset(get_copy = "pub")]
+    size: u64,
+}
+
+impl TopLevelDirRisk {
+    pub fn size_human(&self) -> Size {
+        Size::from_bytes(self.size)
+    }
+}
+
+#[derive(Debug, Clone, Default, Getters)]
+#[getset(get = "pub")]
+pub struct ReplicationReport {
+    /// sorted by size, the largest first
+    contents: Vec<AtRiskContent>,
+    /// sorted by size, the largest first
+    top_level_dirs: Vec<TopLevelDirRisk>,
+}
+
+impl ReplicationReport {
+    pub fn total_size(&self) -> u64 {
+        self.contents.iter().map(AtRiskContent::size).sum()
+    }
+}
+
+impl FSTree {
+    /// returns the directory directly under the walked path `file_node` is in, or the walked path itself
+    /// if the file is directly in it
+    fn top_level_dir(&self, file_node: &FileNode) -> NodeId {
+        let mut node_ids = vec![];
+        let mut parent_id = self.0.get(file_node.node_id()).unwrap().parent();
+        while let Some(node_id) = parent_id {
+            let node = self.0.get(node_id).unwrap();
+            if node.data().metadata.is_none() {
+                break;
+            }
+            node_ids.push(node_id);
+            parent_id = node.parent();
+        }
+        let Some(walked_path_id) = node_ids.pop() else {
+            // the walked path is the file itself
+            return file_node.node_id().clone();
+        };
+        node_ids.pop().unwrap_or(walked_path_id).clone()
+    }
+
+    /// number of copies of the clone group, the files which are not in the tree are looked up in `fs`
+    fn clone_group_copies(
+        &self,
+        clone_group: &CloneGroup,
+        clones_db: &ClonesDB,
+        distinct_devices: bool,
+        fs: &impl FileSystem,
+    ) -> usize {
+        // the not-a-backup files do not count as copies, a content only found in such files has a single copy
+        let backups = clone_group.backups(clones_db);
+        if backups.is_empty() {
+            return 1;
+        }
+        if !distinct_devices {
+            return backups.len();
+        }
+        backups
+            .paths(clones_db)
+            .map(|path| path.to_path_buf())
+            .filter_map(|path| match self.node_with_path(&path) {
+                Ok(node) => node.metadata(),
+                Err(_) => fs
+                    .metadata(&path)
+                    .ok()
+                    .map(|path_metadata| path_metadata.metadata()),
+            })
+            .map(|metadata| metadata.device())
+            .unique()
+            .count()
+    }
+
+    /// lists the unique files and the clone groups of the tree which have fewer than `min_copies` copies, with
+    /// `distinct_devices` the copies on the same device count as one
+    pub fn replication_report(
+        &self,
+        clones_db: &ClonesDB,
+        min_copies: usize,
+        distinct_devices: bool,
+        fs: &impl FileSystem,
+    ) -> ReplicationReport {
+        let mut contents = vec![];
+        let mut group_contents: HashMap<GroupId, Option<usize>> = HashMap::new();
+        let mut top_level_dirs: HashMap<NodeId, u64> = HashMap::new();
+        let mut top_level_dir_groups: HashSet<(NodeId, GroupId)> = HashSet::new();
+        for file_node in self.traverse_file_nodes(TraversalOrder::Pre) {
+            if file_node.metadata().is_none() {
+                continue;
+            }
+            let top_level_dir = self.top_level_dir(&file_node);
+            let size = match clones_db.path_id_clone_group(file_node.path_id()) {
+                None => {
+                    if min_copies <= 1 {
+                        continue;
+                    }
+                    let size = file_node.size().unwrap_or(0);
+                    contents.push(AtRiskContent {
+                        files: vec![file_node.path().to_path_buf()],
+                        size,
+                        copies: 1,
+                    });
+                    size
+                }
+                Some(clone_group) => {
+                    let content_index =
+                        *group_contents.entry(clone_group.id()).or_insert_with(|| {
+                            let copies = self.clone_group_copies(
+                                clone_group,
+                                clones_db,
+                                distinct_devices,
+                                fs,
+                            );
+                            (copies < min_copies).then(|| {
+                                contents.push(AtRiskContent {
+                                    files: clone_group
+                                        .paths(clones_db)
+                                        .map(|path| path.to_path_buf())
+                                        .collect(),
+                                    size: clone_group.file_size(),
+                                    copies,
+                                });
+                                contents.len() - 1
+                            })
+                        });
+                    if content_index.is_none() {
+                        continue;
+                    }
+                    if !top_level_dir_groups.insert((top_level_dir.clone(), clone_group.id())) {
+                        continue;
+                    }
+                    clone_group.file_size()
+                }
+            };
+            *top_level_dirs.entry(top_level_dir).or_default() += size;
+        }
+        contents.sort_unstable_by(|content1, content2| {
+            content2
+                .size
+                .cmp(&content1.size)
+                .then_with(|| content1.files.cmp(&content2.files))
+        });
+        let top_level_dirs = top_level_dirs
+            .into_iter()
+            .map(|(node_id, size)| TopLevelDirRisk {
+                dir: self.node_path(&node_id).unwrap().to_path_buf(),
+                size,
+            })
+            .sorted_unstable_by(|dir1, dir2| {
+                dir2.size
+                    .cmp(&dir1.size)
+                    .then_with(|| dir1.dir.cmp(&dir2.dir))
+            })
+            .collect();
+        ReplicationReport {
+            contents,
+            top_level_dirs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::SystemTime};
+
+    use crate::fs::{
+        test_fixture::{fixture_clones_db, fixture_tree},
+        tree::{metadata::NodeMetadata, PathKind},
+        vfs::{MemoryFileSystem, PathMetadata},
+    };
+
+    use super::*;
+
+    #[test]
+    fn replication_report() {
+        let mut fs = MemoryFileSystem::default();
+        let files = [
+            ("photos/a", 10, 1),
+            ("photos/b", 20, 1),
+            ("photos/unique", 5, 1),
+            ("backup/a", 10, 2),
+            ("backup/b", 20, 1),
+        ];
+        for (inode, (file, size, device)) in files.into_iter().enumerate() {
+            let metadata = NodeMetadata::new(size, SystemTime::UNIX_EPOCH, inode as u64, device);
+            fs.insert(
+                format!("/fixture/{file}"),
+                PathMetadata::new(Some(PathKind::File), metadata),
+            );
+        }
+        let tree = fixture_tree(&fs);
+        let clones_db = fixture_clones_db([
+            (10, ["photos/a", "backup/a"]),
+            (20, ["photos/b", "backup/b"]),
+        ]);
+
+        let report = tree.replication_report(&clones_db, 2, false, &fs);
+        assert_eq!(report.total_size(), 5);
+
+        let report = tree.replication_report(&clones_db, 2, true, &fs);
+        let at_risk_files = report
+            .contents()
+            .iter()
+            .map(|content| content.files().len())
+            .collect_vec();
+        assert_eq!(at_risk_files, vec![2, 1]);
+        let top_level_dirs = report
+            .top_level_dirs()
+            .iter()
+            .map(|dir| (dir.dir().as_path(), dir.size()))
+            .collect_vec();
+        assert_eq!(
+            top_level_dirs,
+            vec![
+                (Path::new("/fixture/photos"), 25),
+                (Path::new("/fixture/backup"), 20)
+            ]
+        );
+    }
+}
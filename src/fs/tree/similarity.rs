@@ -0,0 +1,751 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use getset::{CopyGetters, Getters};
+use itertools::Itertools;
+use size::Size;
+
+use crate::clones::db::{ClonesDB, GroupId};
+
+use super::{FSTree, FilesIterKind, NodeId, NodeKind};
+
+/// number of distinct contents and their total size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct ContentStats {
+    count: u64,
+    size: u64,
+}
+
+impl ContentStats {
+    fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.size += size;
+    }
+}
+
+/// distinct contents of the files contained recursively in a directory,
+/// the files of the same clone group being the same content
+#[derive(Debug, Clone, Default)]
+struct DirContent {
+    clones: HashMap<GroupId, u64>,
+    unique: ContentStats,
+    /// total size of the files, including the files having the same content
+    files_size: u64,
+}
+
+impl DirContent {
+    fn stats(&self) -> ContentStats {
+        ContentStats {
+            count: self.clones.len() as u64 + self.unique.count,
+            size: self.clones.values().sum::<u64>() + self.unique.size,
+        }
+    }
+
+    fn shared_with(&self, other: &DirContent) -> ContentStats {
+        let (smaller, larger) = if self.clones.len() <= other.clones.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let mut shared = ContentStats::default();
+        for (group_id, size) in &smaller.clones {
+            if larger.clones.contains_key(group_id) {
+                shared.add(*size);
+            }
+        }
+        shared
+    }
+}
+
+/// similarity of the content of a directory with the content of another directory
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct DirSimilarity {
+    #[getset(get = "pub")]
+    dir: PathBuf,
+    #[getset(get = "pub")]
+    other_dir: PathBuf,
+    /// contents of `dir`
+    #[getset(get_copy = "pub")]
+    content: ContentStats,
+    /// contents of `other_dir`
+    #[getset(get_copy = "pub")]
+    other_content: ContentStats,
+    /// contents found in both directories
+    #[getset(get_copy = "pub")]
+    shared: ContentStats,
+}
+
+impl DirSimilarity {
+    /// part of the files of `dir` which have a clone in `other_dir`
+    pub fn count_containment(&self) -> f64 {
+        ratio(self.shared.count, self.content.count)
+    }
+
+    /// part of the size of `dir` which has a clone in `other_dir`
+    pub fn size_containment(&self) -> f64 {
+        ratio(self.shared.size, self.content.size)
+    }
+
+    /// number of shared contents over the number of contents of both directories
+    pub fn count_jaccard(&self) -> f64 {
+        ratio(
+            self.shared.count,
+            self.content.count + self.other_content.count - self.shared.count,
+        )
+    }
+
+    /// size of the shared contents over the size of the contents of both directories
+    pub fn size_jaccard(&self) -> f64 {
+        ratio(
+            self.shared.size,
+            self.content.size + self.other_content.size - self.shared.size,
+        )
+    }
+}
+
+fn ratio(value: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        value as f64 / total as f64
+    }
+}
+
+/// files differing between the directories of a `DirSimilarity`
+#[derive(Debug, Clone, Default, Getters)]
+#[getset(get = "pub")]
+pub struct DirDifferences {
+    /// files of `dir` which have no clone in `other_dir`
+    missing: Vec<PathBuf>,
+    /// files of `other_dir` which have no clone in `dir`
+    extra: Vec<PathBuf>,
+}
+
+/// directory whose files all have a clone in another directory containing more
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct SubsetDir {
+    #[getset(get = "pub")]
+    dir: PathBuf,
+    #[getset(get = "pub")]
+    superset_dir: PathBuf,
+    /// size of the files of `dir`, which would be freed by removing it
+    #[getset(get_copy = "pub")]
+    reclaimable_size: u64,
+}
+
+impl SubsetDir {
+    pub fn reclaimable_size_human(&self) -> Size {
+        Size::from_bytes(self.reclaimable_size)
+    }
+}
+
+/// contents of the walked directories of a tree
+struct TreeContents {
+    dirs: HashMap<NodeId, DirContent>,
+    group_dirs: HashMap<GroupId, Vec<NodeId>>,
+}
+
+impl FSTree {
+    fn tree_contents(&self, clones_db: &ClonesDB) -> TreeContents {
+        let node_ids = self
+            .0
+            .traverse_pre_order_ids(self.root_node_id())
+            .unwrap()
+            .collect_vec();
+        let mut dirs: HashMap<NodeId, DirContent> = HashMap::new();
+        // children come after their parent in pre-order so iterating backwards visits a directory after all its children
+        for node_id in node_ids.iter().rev() {
+            let node = self.0.get(node_id).unwrap();
+            let Some(parent_id) = node.parent() else {
+                continue;
+            };
+            let data = node.data();
+            match data.kind {
+                NodeKind::File => {
+                    let size = data.metadata.map_or(0, |metadata| metadata.size());
+                    let parent_content = dirs.entry(parent_id.clone()).or_default();
+                    parent_content.files_size += size;
+                    match clones_db.path_id_clone_group(data.path_id) {
+                        Some(clone_group) => {
+                            parent_content
+                                .clones
+                                .insert(clone_group.id(), clone_group.file_size());
+                        }
+                        None => parent_content.unique.add(size),
+                    }
+                }
+                NodeKind::Directory => {
+                    let Some(content) = dirs.get(node_id).cloned() else {
+                        continue;
+                    };
+                    let parent_content = dirs.entry(parent_id.clone()).or_default();
+                    parent_content.clones.extend(content.clones);
+                    parent_content.unique.count += content.unique.count;
+                    parent_content.unique.size += content.unique.size;
+                    parent_content.files_size += content.files_size;
+                }
+            }
+        }
+        // the directories only inserted as ancestors of the walked paths do not hold all their files
+        dirs.retain(|node_id, _| self.0.get(node_id).unwrap().data().metadata.is_some());
+        let mut group_dirs: HashMap<GroupId, Vec<NodeId>> = HashMap::new();
+        for (node_id, content) in &dirs {
+            for group_id in content.clones.keys() {
+                group_dirs
+                    .entry(*group_id)
+                    .or_default()
+                    .push(node_id.clone());
+            }
+        }
+        TreeContents { dirs, group_dirs }
+    }
+
+    fn nested(&self, node_id1: &NodeId, node_id2: &NodeId) -> bool {
+        let (path_id1, path_id2) = (
+            self.0.get(node_id1).unwrap().data().path_id,
+            self.0.get(node_id2).unwrap().data().path_id,
+        );
+        path_id1.starts_with(path_id2) || path_id2.starts_with(path_id1)
+    }
+
+    fn dir_similarity(
+        &self,
+        contents: &TreeContents,
+        node_id: &NodeId,
+        other_node_id: &NodeId,
+        shared: ContentStats,
+    ) -> DirSimilarity {
+        DirSimilarity {
+            dir: self.node_path(node_id).unwrap().to_path_buf(),
+            other_dir: self.node_path(other_node_id).unwrap().to_path_buf(),
+            content: contents.dirs[node_id].stats(),
+            other_content: contents.dirs[other_node_id].stats(),
+            shared,
+        }
+    }
+
+    /// returns true if the pair is already reported through another pair, which is the case when:
+    /// - replacing one of the directories by its parent shares the same contents with the same or a better similarity
+    /// - replacing one of the directories by one of its subdirectories shares the same contents with a better similarity
+    /// - the parents of the directories have the same or a better similarity
+    fn similar_pair_is_redundant(
+        &self,
+        pair: (&NodeId, &NodeId),
+        similarity: &DirSimilarity,
+        pair_shared: impl Fn(&NodeId, &NodeId) -> Option<ContentStats>,
+        contents: &TreeContents,
+    ) -> bool {
+        let is_better =
+            |node_id: &NodeId, other_node_id: &NodeId, strictly: bool, same_shared: bool| {
+                let Some(shared) = pair_shared(node_id, other_node_id) else {
+                    return false;
+                };
+                if same_shared && shared.count != similarity.shared.count {
+                    return false;
+                }
+                let jaccard = self
+                    .dir_similarity(contents, node_id, other_node_id, shared)
+                    .count_jaccard();
+                match jaccard.total_cmp(&similarity.count_jaccard()) {
+                    Ordering::Greater => true,
+                    Ordering::Equal => !strictly,
+                    Ordering::Less => false,
+                }
+            };
+        let walked_neighbours = |node_id: &NodeId| {
+            let node = self.0.get(node_id).unwrap();
+            node.parent()
+                .map(|parent_id| (parent_id.clone(), false))
+                .into_iter()
+                .chain(
+                    node.children()
+                        .iter()
+                        .map(|child_id| (child_id.clone(), true)),
+                )
+                .filter(|(node_id, _)| contents.dirs.contains_key(node_id))
+                .collect_vec()
+        };
+        let walked_parent = |node_id: &NodeId| {
+            self.0
+                .get(node_id)
+                .unwrap()
+                .parent()
+                .filter(|parent_id| contents.dirs.contains_key(parent_id))
+        };
+        let (node_id, other_node_id) = pair;
+        let parents_are_better = match (walked_parent(node_id), walked_parent(other_node_id)) {
+            (Some(parent_id), Some(other_parent_id)) => {
+                is_better(parent_id, other_parent_id, false, false)
+            }
+            _ => false,
+        };
+        parents_are_better
+            || walked_neighbours(node_id)
+                .iter()
+                .any(|(neighbour_id, strictly)| {
+                    is_better(neighbour_id, other_node_id, *strictly, true)
+                })
+            || walked_neighbours(other_node_id)
+                .iter()
+                .any(|(neighbour_id, strictly)| is_better(node_id, neighbour_id, *strictly, true))
+    }
+
+    /// ranks the walked directories sharing contents with `dir`, outside of it, by the part of the size of `dir`
+    /// they contain, then by similarity
+    /// the directories whose parent or subdirectory shares the same contents with `dir` with a better similarity are left out
+    pub fn similar_dirs(
+        &self,
+        dir: impl AsRef<Path>,
+        clones_db: &ClonesDB,
+    ) -> anyhow::Result<Vec<DirSimilarity>> {
+        let node_id = self.node_with_path(&dir)?.node_id().clone();
+        let contents = self.tree_contents(clones_db);
+        let Some(content) = contents.dirs.get(&node_id) else {
+            return Err(anyhow!(
+                "not a listed directory: {}",
+                dir.as_ref().to_string_lossy()
+            ));
+        };
+        let candidates = content
+            .clones
+            .keys()
+            .flat_map(|group_id| &contents.group_dirs[group_id])
+            .filter(|other_node_id| !self.nested(&node_id, other_node_id))
+            .unique()
+            .map(|other_node_id| {
+                let shared = content.shared_with(&contents.dirs[other_node_id]);
+                (other_node_id.clone(), shared)
+            })
+            .collect::<HashMap<_, _>>();
+        let mut similarities = candidates
+            .iter()
+            .map(|(other_node_id, shared)| {
+                (
+                    other_node_id,
+                    self.dir_similarity(&contents, &node_id, other_node_id, *shared),
+                )
+            })
+            .filter(|(other_node_id, similarity)| {
+                !self.similar_pair_is_redundant(
+                    (&node_id, other_node_id),
+                    similarity,
+                    |pair_node_id, pair_other_node_id| {
+                        if *pair_node_id != node_id {
+                            return None;
+                        }
+                        candidates.get(pair_other_node_id).copied()
+                    },
+                    &contents,
+                )
+            })
+            .map(|(_, similarity)| similarity)
+            .collect_vec();
+        similarities.sort_unstable_by(|similarity1, similarity2| {
+            similarity2
+                .size_containment()
+                .total_cmp(&similarity1.size_containment())
+                .then_with(|| {
+                    similarity2
+                        .count_jaccard()
+                        .total_cmp(&similarity1.count_jaccard())
+                })
+                .then_with(|| similarity1.other_dir.cmp(&similarity2.other_dir))
+        });
+        Ok(similarities)
+    }
+
+    /// ranks the pairs of walked directories sharing contents by the size of the shared contents,
+    /// the directory of each pair is the one with the fewest contents
+    /// the pairs which are already reported through a pair of parents or subdirectories are left out
+    pub fn similar_dir_pairs(&self, clones_db: &ClonesDB) -> Vec<DirSimilarity> {
+        let contents = self.tree_contents(clones_db);
+        let pair_key = |node_id1: &NodeId, node_id2: &NodeId| {
+            let path_ids = (
+                self.0.get(node_id1).unwrap().data().path_id,
+                self.0.get(node_id2).unwrap().data().path_id,
+            );
+            if path_ids.0 <= path_ids.1 {
+                (node_id1.clone(), node_id2.clone())
+            } else {
+                (node_id2.clone(), node_id1.clone())
+            }
+        };
+        let path_id = |node_id: &NodeId| self.0.get(node_id).unwrap().data().path_id;
+        let mut pairs_shared: HashMap<(NodeId, NodeId), ContentStats> = HashMap::new();
+        // only the directories sharing a group with a directory are paired with it, through the group directories index,
+        // each pair being counted from the directory with the lowest path id and the nested pairs being left out
+        for (node_id, content) in &contents.dirs {
+            let mut others_shared: HashMap<&NodeId, ContentStats> = HashMap::new();
+            for (group_id, size) in &content.clones {
+                for other_node_id in &contents.group_dirs[group_id] {
+                    if path_id(node_id) < path_id(other_node_id) {
+                        others_shared.entry(other_node_id).or_default().add(*size);
+                    }
+                }
+            }
+            for (other_node_id, shared) in others_shared {
+                if !self.nested(node_id, other_node_id) {
+                    pairs_shared.insert((node_id.clone(), other_node_id.clone()), shared);
+                }
+            }
+        }
+        let mut similarities = pairs_shared
+            .iter()
+            .map(|((node_id1, node_id2), shared)| {
+                let stats = (
+                    contents.dirs[node_id1].stats(),
+                    contents.dirs[node_id2].stats(),
+                );
+                let (node_id, other_node_id) = if (stats.0.count, self.node_path(node_id1).unwrap())
+                    <= (stats.1.count, self.node_path(node_id2).unwrap())
+                {
+                    (node_id1, node_id2)
+                } else {
+                    (node_id2, node_id1)
+                };
+                (
+                    (node_id, other_node_id),
+                    self.dir_similarity(&contents, node_id, other_node_id, *shared),
+                )
+            })
+            .filter(|(pair, similarity)| {
+                !self.similar_pair_is_redundant(
+                    *pair,
+                    similarity,
+                    |node_id1, node_id2| pairs_shared.get(&pair_key(node_id1, node_id2)).copied(),
+                    &contents,
+                )
+            })
+            .map(|(_, similarity)| similarity)
+            .collect_vec();
+        similarities.sort_unstable_by(|similarity1, similarity2| {
+            similarity2
+                .shared
+                .size
+                .cmp(&similarity1.shared.size)
+                .then_with(|| {
+                    similarity2
+                        .count_jaccard()
+                        .total_cmp(&similarity1.count_jaccard())
+                })
+                .then_with(|| {
+                    (&similarity1.dir, &similarity1.other_dir)
+                        .cmp(&(&similarity2.dir, &similarity2.other_dir))
+                })
+        });
+        similarities
+    }
+
+    /// returns the walked directories, not nested with the directory of `node_id`, containing all its contents
+    fn superset_dirs(&self, contents: &TreeContents, node_id: &NodeId) -> Vec<NodeId> {
+        let content = &contents.dirs[node_id];
+        if content.unique.count > 0 {
+            return vec![];
+        }
+        let Some(rarest_group_dirs) = content
+            .clones
+            .keys()
+            .map(|group_id| &contents.group_dirs[group_id])
+            .min_by_key(|group_dirs| group_dirs.len())
+        else {
+            return vec![];
+        };
+        rarest_group_dirs
+            .iter()
+            .filter(|other_node_id| !self.nested(node_id, other_node_id))
+            .filter(|other_node_id| {
+                let other_content = &contents.dirs[other_node_id];
+                content
+                    .clones
+                    .keys()
+                    .all(|group_id| other_content.clones.contains_key(group_id))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// returns whether each file of the directory of `node_id` has a clone under the same relative path in the directory
+    /// of `other_node_id`
+    fn same_layout(&self, node_id: &NodeId, other_node_id: &NodeId, clones_db: &ClonesDB) -> bool {
+        let (dir, other_dir) = (
+            self.node_path(node_id).unwrap(),
+            self.node_path(other_node_id).unwrap(),
+        );
+        let group_id = |path_id| {
+            clones_db
+                .path_id_clone_group(path_id)
+                .map(|group| group.id())
+        };
+        self.0
+            .traverse_pre_order_ids(node_id)
+            .unwrap()
+            .map(|node_id| self.0.get(&node_id).unwrap().data())
+            .filter(|data| data.is_file())
+            .all(|data| {
+                let other_file = other_dir.join(data.path().strip_prefix(&dir).unwrap());
+                self.path_node_id(other_file).is_ok_and(|other_node_id| {
+                    let other_data = self.0.get(other_node_id).unwrap().data();
+                    other_data.is_file() && group_id(other_data.path_id) == group_id(data.path_id)
+                })
+            })
+    }
+
+    /// lists the walked directories whose files all have a clone in a single other directory which contains more,
+    /// along with the smallest such directories, sorted by reclaimable size
+    /// unless `ignore_names` is set, the clones need to be under the same relative paths in the other directory
+    /// the subdirectories of a directory contained in another directory are not reported as they go with their parent
+    pub fn subset_dirs(&self, clones_db: &ClonesDB, ignore_names: bool) -> Vec<SubsetDir> {
+        let contents = self.tree_contents(clones_db);
+        let dir_supersets = contents
+            .dirs
+            .keys()
+            .map(|node_id| {
+                let mut superset_ids = self.superset_dirs(&contents, node_id);
+                if !ignore_names {
+                    superset_ids
+                        .retain(|superset_id| self.same_layout(node_id, superset_id, clones_db));
+                }
+                (node_id, superset_ids)
+            })
+            .filter(|(_, superset_ids)| !superset_ids.is_empty())
+            .collect::<HashMap<_, _>>();
+        let mut subset_dirs = vec![];
+        for (node_id, superset_ids) in &dir_supersets {
+            let parent_is_contained = self
+                .0
+                .get(node_id)
+                .unwrap()
+                .parent()
+                .is_some_and(|parent_id| dir_supersets.contains_key(parent_id));
+            if parent_is_contained {
+                continue;
+            }
+            let content = &contents.dirs[*node_id];
+            let count = content.stats().count;
+            for superset_id in superset_ids {
+                // identical directories are listed with `identical_dir_groups`
+                if contents.dirs[superset_id].stats().count == count {
+                    continue;
+                }
+                let has_smaller_superset = self
+                    .0
+                    .get(superset_id)
+                    .unwrap()
+                    .children()
+                    .iter()
+                    .any(|child_id| superset_ids.contains(child_id));
+                if !has_smaller_superset {
+                    subset_dirs.push(SubsetDir {
+                        dir: self.node_path(node_id).unwrap().to_path_buf(),
+                        superset_dir: self.node_path(superset_id).unwrap().to_path_buf(),
+                        reclaimable_size: content.files_size,
+                    });
+                }
+            }
+        }
+        subset_dirs.sort_unstable_by(|subset_dir1, subset_dir2| {
+            subset_dir2
+                .reclaimable_size
+                .cmp(&subset_dir1.reclaimable_size)
+                .then_with(|| {
+                    (&subset_dir1.dir, &subset_dir1.superset_dir)
+                        .cmp(&(&subset_dir2.dir, &subset_dir2.superset_dir))
+                })
+        });
+        subset_dirs
+    }
+
+    /// lists the files which differ between the directories of `similarity`
+    pub fn dir_differences(
+        &self,
+        similarity: &DirSimilarity,
+        clones_db: &ClonesDB,
+    ) -> anyhow::Result<DirDifferences> {
+        let dir_files = |dir: &Path| -> anyhow::Result<Vec<(PathBuf, Option<GroupId>)>> {
+            let node = self.node_with_path(dir)?;
+            Ok(node
+                .file_nodes_iter(FilesIterKind::RecursivePreOrder)?
+                .map(|file_node| {
+                    let group_id = clones_db
+                        .path_id_clone_group(file_node.path_id())
+                        .map(|clone_group| clone_group.id());
+                    (file_node.path().to_path_buf(), group_id)
+                })
+                .collect())
+        };
+        let differing_files = |files: Vec<(PathBuf, Option<GroupId>)>,
+                               other_groups: &HashSet<GroupId>| {
+            files
+                .into_iter()
+                .filter(|(_, group_id)| {
+                    group_id.map_or(true, |group_id| !other_groups.contains(&group_id))
+                })
+                .map(|(path, _)| path)
+                .collect_vec()
+        };
+        let files = dir_files(&similarity.dir)?;
+        let other_files = dir_files(&similarity.other_dir)?;
+        let groups = files.iter().filter_map(|(_, group_id)| *group_id).collect();
+        let other_groups = other_files
+            .iter()
+            .filter_map(|(_, group_id)| *group_id)
+            .collect();
+        Ok(DirDifferences {
+            missing: differing_files(files, &other_groups),
+            extra: differing_files(other_files, &groups),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::test_fixture::{fixture_clones_db, fixture_fs, fixture_tree};
+
+    use super::*;
+
+    fn tree_and_clones_db() -> (FSTree, ClonesDB) {
+        let fs = fixture_fs(
+            [
+                "a/f1",
+                "a/f2",
+                "a/f3",
+                "a/f4",
+                "b/sub/f1",
+                "b/sub/f2",
+                "b/sub/f3",
+                "b/sub/other",
+                "c/f1",
+            ]
+            .map(|file| (file, 10)),
+        );
+        let clones_db = fixture_clones_db([
+            (10, vec!["a/f1", "b/sub/f1", "c/f1"]),
+            (10, vec!["a/f2", "b/sub/f2"]),
+            (10, vec!["a/f3", "b/sub/f3"]),
+        ]);
+        (fixture_tree(&fs), clones_db)
+    }
+
+    #[test]
+    fn similar_dirs() {
+        let (tree, clones_db) = tree_and_clones_db();
+        let similarities = tree.similar_dirs("/fixture/a", &clones_db).unwrap();
+        let other_dirs = similarities
+            .iter()
+            .map(|similarity| similarity.other_dir().as_path())
+            .collect_vec();
+        assert_eq!(
+            other_dirs,
+            vec![Path::new("/fixture/b"), Path::new("/fixture/c")]
+        );
+        assert_eq!(similarities[0].size_containment(), 0.75);
+        assert_eq!(similarities[0].count_jaccard(), 0.6);
+        let differences = tree.dir_differences(&similarities[0], &clones_db).unwrap();
+        assert_eq!(differences.missing(), &vec![PathBuf::from("/fixture/a/f4")]);
+        assert_eq!(
+            differences.extra(),
+            &vec![PathBuf::from("/fixture/b/sub/other")]
+        );
+    }
+
+    #[test]
+    fn subset_dirs() {
+        let fs = fixture_fs(
+            [
+                "old/f1",
+                "old/f2",
+                "old/sub/f3",
+                "new/f1",
+                "new/f2",
+                "new/f4",
+                "new/sub/f3",
+                "other/f1",
+            ]
+            .map(|file| (file, 10)),
+        );
+        let tree = fixture_tree(&fs);
+        let clones_db = fixture_clones_db([
+            (10, vec!["old/f1", "new/f1", "other/f1"]),
+            (10, vec!["old/f2", "new/f2"]),
+            (10, vec!["old/sub/f3", "new/sub/f3"]),
+        ]);
+        let subset_dirs = tree
+            .subset_dirs(&clones_db, false)
+            .into_iter()
+            .map(|subset_dir| {
+                (
+                    subset_dir.dir,
+                    subset_dir.superset_dir,
+                    subset_dir.reclaimable_size,
+                )
+            })
+            .collect_vec();
+        assert_eq!(
+            subset_dirs,
+            vec![
+                (
+                    PathBuf::from("/fixture/old"),
+                    PathBuf::from("/fixture/new"),
+                    30
+                ),
+                (
+                    PathBuf::from("/fixture/other"),
+                    PathBuf::from("/fixture/new"),
+                    10
+                ),
+                (
+                    PathBuf::from("/fixture/other"),
+                    PathBuf::from("/fixture/old"),
+                    10
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn subset_dirs_layout() {
+        let fs = fixture_fs(
+            ["old/f1", "old/sub/f2", "new/f1", "new/other/f2", "new/f3"].map(|file| (file, 10)),
+        );
+        let tree = fixture_tree(&fs);
+        let clones_db = fixture_clones_db([
+            (10, vec!["old/f1", "new/f1"]),
+            (10, vec!["old/sub/f2", "new/other/f2"]),
+        ]);
+        let subset_dirs = |ignore_names| {
+            tree.subset_dirs(&clones_db, ignore_names)
+                .into_iter()
+                .map(|subset_dir| (subset_dir.dir, subset_dir.superset_dir))
+                .collect_vec()
+        };
+        // f2 is in another subdirectory of new
+        assert_eq!(subset_dirs(false), vec![]);
+        assert_eq!(
+            subset_dirs(true),
+            vec![(PathBuf::from("/fixture/old"), PathBuf::from("/fixture/new"))]
+        );
+    }
+
+    #[test]
+    fn similar_dir_pairs() {
+        let (tree, clones_db) = tree_and_clones_db();
+        let pairs = tree
+            .similar_dir_pairs(&clones_db)
+            .into_iter()
+            .map(|similarity| (similarity.dir, similarity.other_dir))
+            .collect_vec();
+        assert_eq!(
+            pairs,
+            vec![
+                (PathBuf::from("/fixture/a"), PathBuf::from("/fixture/b")),
+                (PathBuf::from("/fixture/c"), PathBuf::from("/fixture/a")),
+                (PathBuf::from("/fixture/c"), PathBuf::from("/fixture/b")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    io::{self, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use derive_more::Constructor;
+use getset::{CopyGetters, Getters};
+use path_absolutize::Absolutize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error_behavior::ErrorBehavior,
+    fs::vfs::{DirEntry, FileSystem, MemoryFileSystem, PathMetadata},
+};
+
+use super::{metadata::NodeMetadata, FSTree, PathKind, TraversalOrder};
+
+/// bumped whenever the snapshot format changes
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// where to store the tree snapshot and whether to re-read the directories which changed since it was saved
+#[derive(Debug, Clone, Constructor, Getters, CopyGetters)]
+pub struct SnapshotOptions {
+    #[getset(get = "pub")]
+    path: PathBuf,
+    #[getset(get_copy = "pub")]
+    revalidate: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotEntry {
+    name: OsString,
+    kind: PathKind,
+    metadata: NodeMetadata,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotDir {
+    metadata: NodeMetadata,
+    entries: Vec<SnapshotEntry>,
+}
+
+impl SnapshotDir {
+    fn entries(&self, dir: &Path) -> Vec<io::Result<DirEntry>> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                Ok(DirEntry {
+                    path: dir.join(&entry.name),
+                    metadata: Ok(PathMetadata::new(Some(entry.kind), entry.metadata)),
+                })
+            })
+            .collect()
+    }
+}
+
+/// listings of walked directories which can be saved to disk to build the same tree again without walking the directories
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeSnapshot {
+    version: u32,
+    // absolute paths of the walked directories
+    roots: Vec<OsString>,
+    // absolute directory path => directory listing
+    dirs: HashMap<OsString, SnapshotDir>,
+}
+
+impl Default for TreeSnapshot {
+    fn default() -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            roots: vec![],
+            dirs: HashMap::new(),
+        }
+    }
+}
+
+impl TreeSnapshot {
+    /// reads a snapshot file, returns None if the file does not exist
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Option<Self>> {
+        let path = path.as_ref();
+        let file = match fs_err::File::open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let snapshot: Self =
+            bincode::deserialize_from(io::BufReader::new(file)).map_err(|error| {
+                anyhow!(
+                    "failed reading tree snapshot {}: {error}",
+                    path.to_string_lossy()
+                )
+            })?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(anyhow!(
+                "unsupported tree snapshot version {} in {}",
+                snapshot.version,
+                path.to_string_lossy()
+            ));
+        }
+        Ok(Some(snapshot))
+    }
+
+    /// writes the snapshot to a temporary file which is then renamed so that an interrupted write does not corrupt an existing snapshot
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_os_string();
+        tmp_path.push(".tmp");
+        let mut file = io::BufWriter::new(fs_err::File::create(&tmp_path)?);
+        bincode::serialize_into(&mut file, self).map_err(|error| {
+            anyhow!(
+                "failed writing tree snapshot {}: {error}",
+                path.to_string_lossy()
+            )
+        })?;
+        file.flush()?;
+        drop(file);
+        fs_err::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// returns true if `path` is one of the walked directories or is inside one of them
+    pub fn covers(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref().absolutize().unwrap();
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+
+    /// replaces the listings under `roots` with the directories found in `tree` under them
+    pub fn update(&mut self, tree: &FSTree, roots: &[&Path]) {
+        let roots = roots
+            .iter()
+            .filter(|root| root.is_dir())
+            .map(|root| root.absolutize().unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        self.dirs
+            .retain(|dir, _| !roots.iter().any(|root| Path::new(dir).starts_with(root)));
+        self.roots.retain(|old_root| {
+            !roots
+                .iter()
+                .any(|root| Path::new(old_root).starts_with(root))
+        });
+
+        for root in roots {
+            let dir_nodes = tree
+                .traverse_path_directory_nodes(&root, TraversalOrder::Pre, true)
+                .expect("snapshot roots should be part of the tree");
+            for dir_node in dir_nodes {
+                let Some(metadata) = dir_node.metadata() else {
+                    continue;
+                };
+                let entries = dir_node
+                    .child_nodes_iter()
+                    .filter_map(|node| {
+                        let kind = match node.kind() {
+                            super::NodeKind::File => PathKind::File,
+                            super::NodeKind::Directory => PathKind::Directory,
+                        };
+                        Some(SnapshotEntry {
+                            name: node.name().to_os_string(),
+                            kind,
+                            metadata: node.metadata()?,
+                        })
+                    })
+                    .collect();
+                self.dirs.insert(
+                    dir_node.path().as_os_str().to_os_string(),
+                    SnapshotDir { metadata, entries },
+                );
+            }
+            self.roots.push(root.into_os_string());
+        }
+    }
+}
+
+/// file system whose directories listings are taken from a snapshot, the directories missing from the snapshot are listed from `fs`
+/// with `revalidate` the directories whose modification time changed since the snapshot was taken are listed from `fs` too
+struct SnapshotFileSystem<'a, F: FileSystem> {
+    snapshot: &'a TreeSnapshot,
+    revalidate: bool,
+    fs: &'a F,
+}
+
+impl<F: FileSystem> FileSystem for SnapshotFileSystem<'_, F> {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<DirEntry>>> {
+        let abs_dir = dir.absolutize()?;
+        match self.snapshot.dirs.get(abs_dir.as_os_str()) {
+            Some(snapshot_dir)
+                if !self.revalidate
+                    || self.fs.metadata(dir).is_ok_and(|metadata| {
+                        metadata.metadata().modified() == snapshot_dir.metadata.modified()
+                    }) =>
+            {
+                Ok(snapshot_dir.entries(dir))
+            }
+            _ => self.fs.read_dir(dir),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<PathMetadata> {
+        self.fs.metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.fs.read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.fs.canonicalize(path)
+    }
+}
+
+impl MemoryFileSystem {
+    /// builds a file system from the listings recorded in `snapshot`
+    pub fn from_snapshot(snapshot: &TreeSnapshot) -> Self {
+        let mut fs = Self::default();
+        for (dir, snapshot_dir) in &snapshot.dirs {
+            fs.insert(
+                dir,
+                PathMetadata::new(Some(PathKind::Directory), snapshot_dir.metadata),
+            );
+        }
+        for (dir, snapshot_dir) in &snapshot.dirs {
+            for entry in &snapshot_dir.entries {
+                fs.insert(
+                    Path::new(dir).join(&entry.name),
+                    PathMetadata::new(Some(entry.kind), entry.metadata),
+                );
+            }
+        }
+        fs
+    }
+}
+
+impl FSTree {
+    /// same as `extend_from_fs_with_progress` except that the directories listings are taken from `snapshot`
+    /// with `revalidate` the directories whose modification time changed since the snapshot was taken are listed again from `fs`,
+    /// files modified in place do not change their directory modification time and keep the metadata recorded in the snapshot
+    #[allow(clippy::too_many_arguments)]
+    pub fn extend_from_snapshot_with_progress(
+        &mut self,
+        path: impl AsRef<Path>,
+        fs: &impl FileSystem,
+        snapshot: &TreeSnapshot,
+        revalidate: bool,
+        error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
+        progress: impl FnMut(u64, u64),
+    ) -> anyhow::Result<(u64, u64)> {
+        let fs = SnapshotFileSystem {
+            snapshot,
+            revalidate,
+            fs,
+        };
+        self.extend_from_fs_with_progress(path, &fs, error_behavior, threads, progress)
+    }
+
+    /// builds a tree from the listings recorded in `snapshot` without accessing the file system
+    pub fn from_snapshot(snapshot: &TreeSnapshot) -> Self {
+        let mut tree = Self::default();
+        for root in &snapshot.roots {
+            let Some(root_dir) = snapshot.dirs.get(root) else {
+                continue;
+            };
+            let root_node_id = tree
+                .insert_path_impl(Path::new(root), PathKind::Directory)
+                .unwrap();
+            tree.set_node_metadata(&root_node_id, root_dir.metadata);
+            let mut dirs_to_process = vec![(root_node_id, PathBuf::from(root), root_dir)];
+            while let Some((dir_node_id, dir, snapshot_dir)) = dirs_to_process.pop() {
+                for entry in &snapshot_dir.entries {
+                    let path = dir.join(&entry.name);
+                    let node_id = tree
+                        .insert_child_unchecked_impl(&dir_node_id, path.clone(), entry.kind)
+                        .unwrap();
+                    tree.set_node_metadata(&node_id, entry.metadata);
+                    if let Some(child_dir) = snapshot.dirs.get(path.as_os_str()) {
+                        dirs_to_process.push((node_id, path, child_dir));
+                    }
+                }
+            }
+        }
+        tree
+    }
+}
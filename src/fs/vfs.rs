@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use derive_more::Constructor;
+use getset::CopyGetters;
+use path_absolutize::Absolutize;
+
+use crate::path::filter::PathFilter;
+
+use super::tree::{metadata::NodeMetadata, PathKind};
+
+/// kind and metadata of a path, the kind is None for paths which are neither regular files nor directories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Constructor, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct PathMetadata {
+    kind: Option<PathKind>,
+    metadata: NodeMetadata,
+}
+
+impl PathMetadata {
+    pub fn is_file(&self) -> bool {
+        self.kind == Some(PathKind::File)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == Some(PathKind::Directory)
+    }
+}
+
+impl From<&std::fs::Metadata> for PathMetadata {
+    fn from(metadata: &std::fs::Metadata) -> Self {
+        let kind = if metadata.is_file() {
+            Some(PathKind::File)
+        } else if metadata.is_dir() {
+            Some(PathKind::Directory)
+        } else {
+            None
+        };
+        Self::new(kind, NodeMetadata::from(metadata))
+    }
+}
+
+/// entry of a listed directory
+#[derive(Debug)]
+pub struct DirEntry {
+    pub path: PathBuf,
+    pub metadata: io::Result<PathMetadata>,
+}
+
+/// directory listing and metadata operations the trees and directory walkers are built on
+pub trait FileSystem: Sync {
+    /// lists the entries of `dir` with their metadata, symbolic links are not followed
+    /// fails if `dir` cannot be read, the entries which cannot be read are returned as errors
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<DirEntry>>>;
+
+    /// returns the metadata of `path`, symbolic links are followed
+    fn metadata(&self, path: &Path) -> io::Result<PathMetadata>;
+
+    /// returns the target of the symbolic link `path` as stored in the link, fails if `path` is not a symbolic link
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// returns the absolute path of `path` with all the symbolic links resolved, fails if the path does not exist
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|metadata| metadata.is_file())
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|metadata| metadata.is_dir())
+    }
+}
+
+/// the file system of the machine
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<DirEntry>>> {
+        Ok(std::fs::read_dir(dir)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok(DirEntry {
+                    path: entry.path(),
+                    metadata: entry
+                        .metadata()
+                        .map(|metadata| PathMetadata::from(&metadata)),
+                })
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<PathMetadata> {
+        Ok(PathMetadata::from(&std::fs::metadata(path)?))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::canonicalize(path)
+    }
+}
+
+/// file system whose directory listings only contain the entries accepted by `filter`
+#[derive(Debug, Clone, Copy, Constructor)]
+pub struct FilteredFileSystem<'a, F: FileSystem> {
+    fs: &'a F,
+    filter: &'a PathFilter,
+}
+
+impl<F: FileSystem> FilteredFileSystem<'_, F> {
+    /// replaces the metadata of a symbolic link by the metadata of its target, unless the target does not exist
+    /// or is one of the directories the link is in, which would make the walk loop
+    fn follow_symlink(&self, entry: io::Result<DirEntry>) -> io::Result<DirEntry> {
+        let mut entry = entry?;
+        let other_kind = matches!(&entry.metadata, Ok(metadata) if metadata.kind().is_none());
+        if !other_kind || self.fs.read_link(&entry.path).is_err() {
+            return Ok(entry);
+        }
+        let Ok(target_metadata) = self.fs.metadata(&entry.path) else {
+            return Ok(entry);
+        };
+        if target_metadata.is_dir() && self.is_symlink_loop(&entry.path) {
+            log::warn!(
+                "not following the symbolic link {} which points to one of its parent directories",
+                entry.path.to_string_lossy()
+            );
+            return Ok(entry);
+        }
+        entry.metadata = Ok(target_metadata);
+        Ok(entry)
+    }
+
+    fn is_symlink_loop(&self, link: &Path) -> bool {
+        let Ok(target) = self.fs.canonicalize(link) else {
+            return false;
+        };
+        link.ancestors()
+            .skip(1)
+            .any(|dir| self.fs.canonicalize(dir).is_ok_and(|dir| dir == target))
+    }
+}
+
+impl<F: FileSystem> FileSystem for FilteredFileSystem<'_, F> {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<DirEntry>>> {
+        let entries = self.fs.read_dir(dir)?;
+        if self.filter.is_empty() {
+            return Ok(entries);
+        }
+        let abs_dir = dir.absolutize()?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| {
+                if self.filter.follows_symlinks() {
+                    self.follow_symlink(entry)
+                } else {
+                    entry
+                }
+            })
+            .filter(|entry| {
+                // the entries which cannot be read are kept so that their error is handled by the caller
+                let Ok(DirEntry {
+                    path,
+                    metadata: Ok(metadata),
+                }) = entry
+                else {
+                    return true;
+                };
+                let Some(name) = path.file_name() else {
+                    return true;
+                };
+                let abs_path = abs_dir.join(name);
+                match metadata.kind() {
+                    Some(PathKind::File) => self
+                        .filter
+                        .accepts_file_metadata(abs_path, &metadata.metadata()),
+                    Some(PathKind::Directory) => self
+                        .filter
+                        .accepts_dir_metadata(abs_path, &metadata.metadata()),
+                    None => true,
+                }
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<PathMetadata> {
+        self.fs.metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.fs.read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.fs.canonicalize(path)
+    }
+}
+
+#[derive(Debug)]
+struct MemoryEntry {
+    metadata: PathMetadata,
+    children: Vec<PathBuf>,
+}
+
+/// file system held in memory, used to analyse recorded listings and to build test fixtures
+#[derive(Debug, Default)]
+pub struct MemoryFileSystem {
+    entries: HashMap<PathBuf, MemoryEntry>,
+    inode_counter: u64,
+}
+
+impl MemoryFileSystem {
+    /// inserts `path` with its metadata, the missing ancestors of `path` are inserted as directories
+    pub fn insert(&mut self, path: impl Into<PathBuf>, metadata: PathMetadata) {
+        let path = path.into();
+        if let Some(entry) = self.entries.get_mut(&path) {
+            entry.metadata = metadata;
+            return;
+        }
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            if !self.entries.contains_key(parent) {
+                self.insert_dir(parent);
+            }
+            self.entries
+                .get_mut(parent)
+                .unwrap()
+                .children
+                .push(path.clone());
+        }
+        self.entries.insert(
+            path,
+            MemoryEntry {
+                metadata,
+                children: vec![],
+            },
+        );
+    }
+
+    pub fn insert_dir(&mut self, path: impl Into<PathBuf>) {
+        let metadata = self.new_metadata(0);
+        self.insert(path, PathMetadata::new(Some(PathKind::Directory), metadata));
+    }
+
+    pub fn insert_file(&mut self, path: impl Into<PathBuf>, size: u64) {
+        let metadata = self.new_metadata(size);
+        self.insert(path, PathMetadata::new(Some(PathKind::File), metadata));
+    }
+
+    fn new_metadata(&mut self, size: u64) -> NodeMetadata {
+        self.inode_counter += 1;
+        NodeMetadata::new(size, SystemTime::UNIX_EPOCH, self.inode_counter, 0)
+    }
+
+    fn entry(&self, path: &Path) -> io::Result<&MemoryEntry> {
+        self.entries.get(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such file or directory: {}", path.to_string_lossy()),
+            )
+        })
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<DirEntry>>> {
+        let entry = self.entry(dir)?;
+        if !entry.metadata.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("not a directory: {}", dir.to_string_lossy()),
+            ));
+        }
+        Ok(entry
+            .children
+            .iter()
+            .map(|child| {
+                Ok(DirEntry {
+                    path: child.clone(),
+                    metadata: Ok(self.entries[child].metadata),
+                })
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<PathMetadata> {
+        Ok(self.entry(path)?.metadata)
+    }
+
+    /// there are no symbolic links in memory
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.entry(path)?;
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("not a symbolic link: {}", path.to_string_lossy()),
+        ))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.entry(path)?;
+        Ok(path.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::*;
+    use crate::{
+        error_behavior::ErrorBehavior,
+        fs::{
+            test_fixture::{fixture_clones_db, fixture_fs, fixture_tree},
+            Tree,
+        },
+        path::HashedAbsolutePath,
+    };
+
+    fn fixture() -> MemoryFileSystem {
+        let mut fs = fixture_fs([
+            ("a/f1", 10),
+            ("a/f2", 20),
+            ("b/f1", 10),
+            ("b/f2", 20),
+            ("b/unique", 5),
+        ]);
+        fs.insert_dir("/fixture/empty");
+        fs
+    }
+
+    #[test]
+    fn tree_from_memory_file_system() {
+        let mut tree = Tree::default();
+        let counts = tree
+            .extend_from_fs_with_progress(
+                "/fixture",
+                &fixture(),
+                ErrorBehavior::Stop,
+                NonZeroUsize::new(2).unwrap(),
+                |_, _| {},
+            )
+            .unwrap();
+        assert_eq!(counts, (4, 5));
+        let file = tree.node_with_path("/fixture/b/unique").unwrap();
+        assert_eq!(file.upgrade_to_file_node().size(), Some(5));
+    }
+
+    #[test]
+    fn clone_dirs_from_memory_file_system() {
+        let tree = fixture_tree(&fixture());
+        let clones_db = fixture_clones_db([(10, ["a/f1", "b/f1"]), (20, ["a/f2", "b/f2"])]);
+        let clone_dir_paths = |against: Option<&[HashedAbsolutePath]>,
+                               scope: &[HashedAbsolutePath]| {
+            tree.clone_dirs("/fixture", &clones_db, true, against, scope)
+                .unwrap()
+                .iter()
+                .map(|clone_dir| clone_dir.path().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(clone_dir_paths(None, &[]), vec![Path::new("/fixture/a")]);
+        let archive = [HashedAbsolutePath::from("/fixture/b")];
+        assert_eq!(
+            clone_dir_paths(Some(&archive), &[]),
+            vec![Path::new("/fixture/a")]
+        );
+        let trash = [HashedAbsolutePath::from("/fixture/empty")];
+        assert!(clone_dir_paths(Some(&trash), &[]).is_empty());
+        let project = ["/fixture/a", "/fixture/b"].map(HashedAbsolutePath::from);
+        assert!(clone_dir_paths(None, &project).is_empty());
+    }
+}
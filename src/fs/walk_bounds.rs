@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use path_absolutize::Absolutize;
+
+use super::{
+    tree::metadata::NodeMetadata,
+    vfs::{FileSystem, RealFileSystem},
+};
+
+/// walked directory the depths are counted from, along with its device
+#[derive(Debug, Clone)]
+struct WalkRoot {
+    path: PathBuf,
+    device: Option<u64>,
+}
+
+/// depth and file system limits of the walks, the depth of a path being its number of components below the
+/// walked directory it is in: the files directly in a walked directory are at depth 1
+#[derive(Debug, Clone, Default)]
+pub struct WalkBounds {
+    min_depth: Option<usize>,
+    max_depth: Option<usize>,
+    one_file_system: bool,
+    roots: Vec<WalkRoot>,
+    /// devices of the directories read so far to check the files of the clones list
+    dir_devices: Arc<Mutex<HashMap<PathBuf, Option<u64>>>>,
+}
+
+impl WalkBounds {
+    pub fn new(min_depth: Option<usize>, max_depth: Option<usize>, one_file_system: bool) -> Self {
+        Self {
+            min_depth,
+            max_depth,
+            one_file_system,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.min_depth.is_none() && self.max_depth.is_none() && !self.one_file_system
+    }
+
+    /// returns the bounds relative to the walked directories `roots`, the nested roots being counted from the deepest one
+    pub fn with_roots<'a>(&self, roots: impl IntoIterator<Item = &'a Path>) -> Self {
+        let roots = roots
+            .into_iter()
+            .filter_map(|root| {
+                let path = root.absolutize().ok()?.to_path_buf();
+                let device = if self.one_file_system {
+                    RealFileSystem
+                        .metadata(&path)
+                        .ok()
+                        .map(|metadata| metadata.metadata().device())
+                } else {
+                    None
+                };
+                Some(WalkRoot { path, device })
+            })
+            .collect();
+        Self {
+            roots,
+            dir_devices: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// returns the deepest root containing the absolute `path` along with the depth of `path` under it
+    fn root_and_depth(&self, path: &Path) -> Option<(&WalkRoot, usize)> {
+        self.roots
+            .iter()
+            .filter_map(|root| {
+                let depth = path.strip_prefix(&root.path).ok()?.components().count();
+                Some((root, depth))
+            })
+            .min_by_key(|&(_, depth)| depth)
+    }
+
+    fn accepts_file_depth(&self, depth: usize) -> bool {
+        !self.min_depth.is_some_and(|min_depth| depth < min_depth)
+            && !self.max_depth.is_some_and(|max_depth| depth > max_depth)
+    }
+
+    /// returns true if the absolute `dir` can hold files within the maximum depth and is on the device of its root,
+    /// the paths outside of the roots are accepted
+    pub fn accepts_dir(&self, dir: &Path, metadata: &NodeMetadata) -> bool {
+        let Some((root, depth)) = self.root_and_depth(dir) else {
+            return true;
+        };
+        let other_device = self.one_file_system
+            && root
+                .device
+                .is_some_and(|device| device != metadata.device());
+        !self.max_depth.is_some_and(|max_depth| depth >= max_depth) && !other_device
+    }
+
+    /// returns true if the depth of the absolute `file` is within the bounds, the paths outside of the roots are accepted
+    pub fn accepts_file(&self, file: &Path) -> bool {
+        match self.root_and_depth(file) {
+            Some((_, depth)) => self.accepts_file_depth(depth),
+            None => true,
+        }
+    }
+
+    fn dir_device(&self, dir: &Path) -> Option<u64> {
+        *self
+            .dir_devices
+            .lock()
+            .unwrap()
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                RealFileSystem
+                    .metadata(dir)
+                    .ok()
+                    .map(|metadata| metadata.metadata().device())
+            })
+    }
+
+    /// returns true if the absolute `file` of the clones list is under one of the roots and within the bounds,
+    /// the device being the one of its directory
+    pub fn contains(&self, file: &Path) -> bool {
+        let Some((root, depth)) = self.root_and_depth(file) else {
+            return false;
+        };
+        let other_device = self.one_file_system
+            && root.device.is_some()
+            && file
+                .parent()
+                .is_some_and(|dir| self.dir_device(dir) != root.device);
+        self.accepts_file_depth(depth) && !other_device
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[test]
+    fn depth_bounds() {
+        let bounds = WalkBounds::new(Some(2), Some(3), false)
+            .with_roots([Path::new("/a"), Path::new("/a/b/c")]);
+        let metadata = NodeMetadata::new(0, SystemTime::UNIX_EPOCH, 1, 1);
+        assert!(!bounds.accepts_file(Path::new("/a/f")));
+        assert!(bounds.accepts_file(Path::new("/a/b/f")));
+        assert!(bounds.accepts_file(Path::new("/a/b/d/f")));
+        assert!(!bounds.accepts_file(Path::new("/a/b/d/e/f")));
+        // counted from the nested root
+        assert!(bounds.accepts_file(Path::new("/a/b/c/d/e/f")));
+        assert!(bounds.accepts_dir(Path::new("/a/b/d"), &metadata));
+        assert!(!bounds.accepts_dir(Path::new("/a/b/d/e"), &metadata));
+        assert!(bounds.contains(Path::new("/a/b/f")));
+        assert!(!bounds.contains(Path::new("/x/b/f")));
+        assert!(WalkBounds::default().is_unbounded());
+    }
+
+    #[test]
+    fn one_file_system_bounds() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("a")).unwrap();
+        let bounds = WalkBounds::new(None, None, true).with_roots([root]);
+        // the device of the directory of a clones list file is the one of its root
+        assert!(bounds.contains(&root.join("a/f")));
+        assert!(!bounds.contains(Path::new("/elsewhere/f")));
+    }
+}
@@ -0,0 +1,24 @@
+//! query engine of the `lsc` utility: lists clone and unique files and directories from a clones list
+//! produced by the [`fclones`](https://github.com/pkolaczk/fclones) utility
+//!
+//! - [`clones::db::ClonesDB`] loads a clones list and answers which files are clones of which
+//! - [`fs::Tree`] holds the listing of the directories to analyse, built from the real file system or
+//!   from any [`fs::vfs::FileSystem`] implementation
+//! - [`fs::Tree::clone_dirs`] and [`fs::Tree::clone_dir_groups`] find the directories whose files all have clones outside of them
+//! - the [`paths::Clones`] trait lists the clones of a set of paths
+//! - [`config::Config`] holds the settings read from the configuration file
+//! - [`catalog::Catalog`] records the files of a drive so that it can be queried while the drive is not attached
+//!
+//! the functions doing long operations take a progress callback and never print anything, except for the errors
+//! displayed with [`error_behavior::ErrorBehavior::Display`]
+
+pub mod catalog;
+pub mod clones;
+pub mod config;
+pub mod consolidate;
+pub mod error_behavior;
+pub mod fs;
+mod hash;
+mod parallel;
+pub mod path;
+pub mod paths;
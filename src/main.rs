@@ -1,45 +1,76 @@
-#![allow(dead_code)]
-
 use std::{
     borrow::Borrow,
     cmp::Ord,
     env::current_dir,
     io::{self, Write},
-    os::unix::prelude::MetadataExt,
-    path::Path,
+    path::{Path, PathBuf},
     process,
 };
 
+use anyhow::anyhow;
 use clap::Parser;
 use cli::{CommandArgsPaths, CommonOptions};
-use clones::db::ClonesDB;
 use crossterm::cursor;
 use env_logger::fmt::Color;
-use fs::{
-    dir,
-    tree::clones::{CloneDir, CloneDirs, RefDir},
-};
 use itertools::Itertools;
-use paths::{Clones, PathRefs, TreeWithProgress};
+use lsclones::{
+    catalog::{Catalog, CatalogsFileSystem},
+    clones::db::{CloneGroupFileCountAndSize, CloneGroupReclaimable, ClonesDB},
+    config::{self, Config},
+    consolidate,
+    error_behavior::ErrorBehavior,
+    fs::{
+        self, dir,
+        symlinks::{self, Symlink, SymlinkPolicy},
+        tree::{
+            clones::{CloneDir, CloneDirGroups, CloneDirs, RefDir},
+            merge_plan::MergeAction,
+            similarity::DirSimilarity,
+        },
+        vfs::{FileSystem, FilteredFileSystem, RealFileSystem},
+    },
+    path::{filter::PathFilter, glob::PathGlobs, HashedAbsolutePath},
+    paths::{Clones, PathRefs},
+};
+use path_absolutize::Absolutize;
+use progress::CountProgress;
 use size::Size;
 
 mod call_rate_limiter;
 mod cli;
-mod clones;
-mod error_behavior;
-mod fs;
-mod hash;
 mod log_level;
-mod path;
-mod paths;
+mod progress;
 
-use crate::{
-    cli::{Cli, Commands},
-    error_behavior::ErrorBehavior,
-    fs::tree::clones::CloneDirGroups,
-};
+use crate::cli::{CatalogCommands, Cli, Commands};
+
+/// returns the absolute reference roots, or None if there are none, warning about the roots the clones are unknown in
+fn reference_roots(
+    roots: &[PathBuf],
+    clones_db: &ClonesDB,
+) -> io::Result<Option<Vec<HashedAbsolutePath>>> {
+    if roots.is_empty() {
+        return Ok(None);
+    }
+    let roots = roots
+        .iter()
+        .map(|root| Ok(HashedAbsolutePath::from(root.absolutize()?.as_ref())))
+        .collect::<io::Result<Vec<_>>>()?;
+    for root in &roots {
+        if !clones_db.was_scanned(root.to_path_buf()) {
+            log::warn!(
+                "{} is outside of the paths scanned to build the clones list",
+                root
+            );
+        }
+    }
+    Ok(Some(roots))
+}
 
-fn files_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
+fn files_command(
+    args: &Commands,
+    clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+) -> anyhow::Result<()> {
     let Commands::Files {
         global_options,
         unique,
@@ -48,6 +79,8 @@ fn files_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
         inside,
         outside,
         inside_only,
+        against,
+        single_scope,
         null_line_terminator,
     } = args
     else {
@@ -56,7 +89,35 @@ fn files_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
 
     eprintln!();
 
+    let against = reference_roots(against, clones_db)?;
+
     let paths = paths.paths();
+
+    // the modification times are only known for the walked files: the files of the specified paths which are
+    // left out of the walk are removed from the clones list
+    let path_filter = global_options.path_filter()?;
+    let time_filtered_clones_db;
+    let clones_db = if path_filter.has_time_criteria() && !*unique {
+        let file_tree = progress::tree_with_progress(
+            &paths,
+            fs,
+            ErrorBehavior::Display,
+            global_options.threads(),
+            global_options.tree_snapshot().as_ref(),
+            &path_filter,
+        )?;
+        let roots = paths
+            .iter()
+            .map(|path| Ok(path.absolutize()?.to_path_buf()))
+            .collect::<io::Result<Vec<_>>>()?;
+        time_filtered_clones_db = clones_db.without_files(|file, _| {
+            roots.iter().any(|root| file.starts_with(root)) && !file_tree.contains_file(file)
+        });
+        &time_filtered_clones_db
+    } else {
+        clones_db
+    };
+
     let recursive = global_options.recursive();
     let display_stats = global_options.stats();
 
@@ -68,21 +129,31 @@ fn files_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
 
     if *map {
         let mut reclaimable_size = 0;
-        let clone_groups = paths.clone_groups(recursive, clones_db);
+        let clone_groups = paths.clone_groups(
+            recursive,
+            *single_scope,
+            path_filter.bounds(),
+            clones_db,
+            fs,
+        );
         for (index, clone_group) in clone_groups.iter().enumerate() {
+            let outside_clones = match &against {
+                Some(roots) => clone_group.outside().filter_in_dirs(roots, clones_db),
+                None => clone_group.outside().clone(),
+            };
             if (!(*inside || *inside_only) || clone_group.inside().len() > 1)
-                && !(*outside && clone_group.outside().is_empty())
+                && !(*outside && outside_clones.is_empty())
             {
-                for file in clone_group.inside().iter() {
-                    print_path(file, path_print_style, *null_line_terminator);
+                for file in clone_group.inside().paths(clones_db) {
+                    print_path(file.to_path_buf(), path_print_style, *null_line_terminator);
                 }
                 file_count += clone_group.inside().len();
                 total_size += clone_group.inside().total_size();
                 reclaimable_size += clone_group.inside().reclaimable_size();
-                if !(*inside_only || clone_group.outside().is_empty()) {
+                if !(*inside_only || outside_clones.is_empty()) {
                     bunt::eprintln!("{$green}=>{/$}");
-                    for file in clone_group.outside().iter() {
-                        print_path(file, path_print_style, *null_line_terminator);
+                    for file in outside_clones.paths(clones_db) {
+                        print_path(file.to_path_buf(), path_print_style, *null_line_terminator);
                     }
                 }
                 if index < clone_groups.len() - 1 {
@@ -99,13 +170,21 @@ fn files_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
             );
         }
     } else if *unique {
-        let file_tree = paths.tree_with_progress(ErrorBehavior::Display)?;
-        for file in file_tree.unique_files_iter(clones_db).sorted() {
-            if global_options.stats() {
-                file_count += 1;
-                total_size += std::fs::metadata(file)?.size();
-            }
-            print_path(file, path_print_style, *null_line_terminator);
+        let file_tree = progress::tree_with_progress(
+            &paths,
+            fs,
+            ErrorBehavior::Display,
+            global_options.threads(),
+            global_options.tree_snapshot().as_ref(),
+            &path_filter,
+        )?;
+        for file in file_tree
+            .unique_files_iter(clones_db)
+            .sorted_unstable_by_key(|file| file.path())
+        {
+            file_count += 1;
+            total_size += file.size().unwrap_or_default();
+            print_path(file.path(), path_print_style, *null_line_terminator);
         }
         if display_stats {
             eprintln!();
@@ -116,12 +195,14 @@ fn files_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
             );
         }
     } else if *inside {
-        let (stats, clones) = paths.inside_clones(recursive, clones_db);
+        let (stats, clones) = paths.inside_clones(
+            recursive,
+            *single_scope,
+            path_filter.bounds(),
+            clones_db,
+            fs,
+        );
         for file in clones.into_iter().sorted() {
-            if global_options.stats() {
-                file_count += 1;
-                total_size += std::fs::metadata(file)?.size();
-            }
             print_path(file, path_print_style, *null_line_terminator);
         }
         if display_stats {
@@ -132,7 +213,7 @@ fn files_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
             );
         }
     } else {
-        let (stats, clones) = paths.clones(recursive, clones_db);
+        let (stats, clones) = paths.clones(recursive, path_filter.bounds(), clones_db, fs);
         let file_count = clones.len();
         for file in clones.into_iter().sorted() {
             print_path(file, path_print_style, *null_line_terminator);
@@ -163,6 +244,13 @@ impl<'a> PathPrintStyle<'a> {
             PathPrintStyle::RelativeTo(current_dir)
         }
     }
+
+    fn apply<'p>(&self, path: &'p Path) -> &'p Path {
+        match self {
+            PathPrintStyle::Absolute => path,
+            PathPrintStyle::RelativeTo(prefix) => path.strip_prefix(prefix).unwrap_or(path),
+        }
+    }
 }
 
 fn print_path(path: impl AsRef<Path>, style: PathPrintStyle, null_line_terminator: bool) {
@@ -177,9 +265,9 @@ fn print_path(path: impl AsRef<Path>, style: PathPrintStyle, null_line_terminato
     }
 }
 
-fn print_clone_dir_path<'a>(clone_dir: impl Borrow<CloneDir<'a>>, style: PathPrintStyle) {
+fn print_clone_dir_path(clone_dir: impl Borrow<CloneDir>, style: PathPrintStyle) {
     let clone_dir = clone_dir.borrow();
-    let mut path = clone_dir.path();
+    let mut path = clone_dir.path().as_path();
     if let PathPrintStyle::RelativeTo(prefix) = style {
         if let Ok(rel_path) = path.strip_prefix(prefix) {
             path = rel_path;
@@ -202,7 +290,8 @@ fn print_ref_dir_path<'a>(
     missing_files: usize,
     extra_files: usize,
 ) {
-    let mut path = ref_dir.borrow().path().as_path();
+    let path = ref_dir.borrow().path().to_path_buf();
+    let mut path = path.as_path();
     if let PathPrintStyle::RelativeTo(prefix) = style {
         path = path.strip_prefix(prefix).unwrap_or(path);
     }
@@ -231,6 +320,57 @@ fn print_ref_file(path: impl AsRef<Path>, style: PathPrintStyle, ref_file_type:
     }
 }
 
+/// prints the dangling symbolic links in `dirs` and the ones pointing into `clone_dirs` from outside of them
+fn print_symlinks_report<'a>(
+    dirs: &PathRefs,
+    clone_dirs: impl IntoIterator<Item = &'a Path>,
+    path_filter: &PathFilter,
+    error_behavior: ErrorBehavior,
+    style: PathPrintStyle,
+) -> anyhow::Result<()> {
+    let mut symlinks = vec![];
+    for dir in dirs {
+        let path_filter = path_filter.with_roots([dir]);
+        let fs = FilteredFileSystem::new(&RealFileSystem, &path_filter);
+        symlinks.extend(symlinks::find_symlinks(
+            &fs,
+            dir.absolutize()?,
+            error_behavior,
+        )?);
+    }
+    symlinks.sort_unstable_by(|link1, link2| Ord::cmp(link1.path(), link2.path()));
+    let clone_dirs = clone_dirs.into_iter().collect_vec();
+    let resolved_clone_dirs = clone_dirs
+        .iter()
+        .filter_map(|dir| RealFileSystem.canonicalize(dir).ok())
+        .collect_vec();
+
+    let print_symlink = |symlink: &Symlink| {
+        bunt::eprintln!(
+            "{} {$green}->{/$} {}",
+            style.apply(symlink.path()).to_string_lossy(),
+            symlink.target().to_string_lossy()
+        );
+    };
+    eprintln!();
+    bunt::eprintln!("{$bold}dangling symlinks:{/$}");
+    for symlink in symlinks.iter().filter(|symlink| symlink.is_dangling()) {
+        print_symlink(symlink);
+    }
+    // the links inside the clone dirs go away with them
+    eprintln!();
+    bunt::eprintln!("{$bold}symlinks pointing into the clone dirs:{/$}");
+    for symlink in symlinks.iter().filter(|symlink| {
+        resolved_clone_dirs
+            .iter()
+            .any(|dir| symlink.points_into(dir))
+            && !clone_dirs.iter().any(|dir| symlink.path().starts_with(dir))
+    }) {
+        print_symlink(symlink);
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 fn dirs_command_clones(
     dirs: PathRefs,
@@ -240,22 +380,65 @@ fn dirs_command_clones(
     ref_details: bool,
     error_behavior: ErrorBehavior,
     clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+    against: Option<&[HashedAbsolutePath]>,
+    single_scope: bool,
+    hide_filtered: bool,
     null_line_terminator: bool,
 ) -> anyhow::Result<()> {
-    let file_tree = dirs.tree_with_progress(error_behavior)?;
+    let path_filter = global_options
+        .path_filter()?
+        .with_roots(dirs.iter().copied());
+    // when only hiding, the filtered files are part of the clone dirs computation, the ignored ones never are
+    let walk_filter = if hide_filtered {
+        path_filter.ignore_files_only()
+    } else {
+        path_filter.clone()
+    };
+    let file_tree = progress::tree_with_progress(
+        &dirs,
+        fs,
+        error_behavior,
+        global_options.threads(),
+        global_options.tree_snapshot().as_ref(),
+        &walk_filter,
+    )?;
     eprintln!();
+    // the walked files are checked along with the size and modification time recorded in the tree
+    let accepts_walked_file = |file: &Path| match file_tree
+        .node_with_path(file)
+        .ok()
+        .and_then(|node| node.metadata())
+    {
+        Some(metadata) => path_filter.accepts_file_metadata(file, &metadata),
+        None => path_filter.accepts_file(file),
+    };
+    let displayed = |clone_dir: &CloneDir| {
+        clone_dir
+            .file_paths(clones_db)
+            .any(|file| accepts_walked_file(&file.to_path_buf()))
+    };
 
     let current_dir = current_dir().unwrap();
     let path_print_style = PathPrintStyle::new(global_options, current_dir.as_path());
 
+    let scope = if single_scope {
+        dirs.iter()
+            .map(|&dir| HashedAbsolutePath::from(dir))
+            .collect_vec()
+    } else {
+        vec![]
+    };
+
     if map {
         let clone_dir_groups = dirs
             .iter()
             .flat_map(|dir| {
                 file_tree
-                    .clone_dir_groups(dir, clones_db, global_options.recursive())
+                    .clone_dir_groups(dir, clones_db, global_options.recursive(), against, &scope)
                     .unwrap()
             })
+            .filter(|clone_dir_group| displayed(clone_dir_group.first().unwrap()))
             .collect::<CloneDirGroups>();
 
         for (index, clone_dir_group) in clone_dir_groups.iter().enumerate() {
@@ -263,12 +446,25 @@ fn dirs_command_clones(
                 print_clone_dir_path(dir, path_print_style);
             }
             if show_refs || ref_details {
-                let ref_dirs = clone_dir_group.ref_dirs();
+                let ref_dirs = clone_dir_group.ref_dirs(clones_db);
                 if !ref_dirs.is_empty() {
                     bunt::println!("{$green}=>{/$}");
                     for ref_dir in ref_dirs {
-                        let missing_files = ref_dir.missing();
-                        let extra_files = ref_dir.extra();
+                        let missing_files = ref_dir
+                            .missing(clones_db)
+                            .into_iter()
+                            .filter(|&file| {
+                                path_filter.accepts_clone(
+                                    clones_db.file_path(file).to_path_buf(),
+                                    clones_db.file_id_clone_group(file).file_size(),
+                                )
+                            })
+                            .collect_vec();
+                        let extra_files = ref_dir
+                            .extra(clones_db, &file_tree, error_behavior)?
+                            .into_iter()
+                            .filter(|file| accepts_walked_file(file))
+                            .collect_vec();
                         print_ref_dir_path(
                             &ref_dir,
                             path_print_style,
@@ -277,7 +473,11 @@ fn dirs_command_clones(
                         );
                         if ref_details {
                             for file in missing_files {
-                                print_ref_file(file, path_print_style, RefFileType::Missing)
+                                print_ref_file(
+                                    clones_db.file_path(file).to_path_buf(),
+                                    path_print_style,
+                                    RefFileType::Missing,
+                                )
                             }
                             for file in extra_files {
                                 print_ref_file(file, path_print_style, RefFileType::Extra)
@@ -298,14 +498,27 @@ fn dirs_command_clones(
                 clone_dir_groups.dir_count(), clone_dir_groups.size_human(), clone_dir_groups.minimum_reclaimable_size_human()
             );
         }
+
+        if global_options.symlinks() == SymlinkPolicy::Report {
+            print_symlinks_report(
+                &dirs,
+                clone_dir_groups
+                    .iter()
+                    .flat_map(|group| group.iter().map(|dir| dir.path().as_path())),
+                &walk_filter,
+                error_behavior,
+                path_print_style,
+            )?;
+        }
     } else {
         let mut clone_dirs = dirs
             .iter()
             .flat_map(|dir| {
                 file_tree
-                    .clone_dirs(dir, clones_db, global_options.recursive())
+                    .clone_dirs(dir, clones_db, global_options.recursive(), against, &scope)
                     .unwrap()
             })
+            .filter(displayed)
             .collect::<CloneDirs>();
 
         clone_dirs.sort_unstable_by(|cd1, cd2| Ord::cmp(cd1.path(), cd2.path()));
@@ -321,6 +534,16 @@ fn dirs_command_clones(
                 clone_dirs.size_human()
             );
         }
+
+        if global_options.symlinks() == SymlinkPolicy::Report {
+            print_symlinks_report(
+                &dirs,
+                clone_dirs.iter().map(|dir| dir.path().as_path()),
+                &walk_filter,
+                error_behavior,
+                path_print_style,
+            )?;
+        }
     }
 
     Ok(())
@@ -329,20 +552,36 @@ fn dirs_command_clones(
 fn dirs_command_unique(
     dirs: PathRefs,
     global_options: &CommonOptions,
+    error_behavior: ErrorBehavior,
     clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
     null_line_terminator: bool,
 ) -> anyhow::Result<()> {
+    let path_filter = global_options.path_filter()?;
     let mut udirs = vec![];
-    let mut dir_count = 0;
-    let mut total_size = 0;
+    for dir in &dirs {
+        udirs.extend(dir::unique_dirs(
+            &FilteredFileSystem::new(fs, &path_filter.with_roots([dir])),
+            dir,
+            global_options.recursive(),
+            clones_db,
+        )?);
+    }
 
-    for dir in dirs {
-        for udir in dir::unique_dirs(dir, global_options.recursive(), clones_db)? {
-            if global_options.stats() {
-                dir_count += 1;
-                total_size += dir::size(&udir);
-            }
-            udirs.push(udir);
+    let (dir_count, mut total_size) = (udirs.len(), 0);
+    if global_options.stats() {
+        let mut file_tree = progress::tree_with_progress(
+            &dirs,
+            fs,
+            error_behavior,
+            global_options.threads(),
+            global_options.tree_snapshot().as_ref(),
+            &global_options.path_filter()?,
+        )?;
+        file_tree.compute_dir_totals(clones_db);
+        for udir in &udirs {
+            let dir_node = file_tree.node_with_path(udir)?.upgrade_to_directory_node();
+            total_size += dir_node.totals().unwrap().size();
         }
     }
 
@@ -366,13 +605,125 @@ fn dirs_command_unique(
     Ok(())
 }
 
-fn dirs_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
+fn dirs_command_identical(
+    dirs: PathRefs,
+    global_options: &CommonOptions,
+    error_behavior: ErrorBehavior,
+    clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+    ignore_names: bool,
+) -> anyhow::Result<()> {
+    let mut file_tree = progress::tree_with_progress(
+        &dirs,
+        fs,
+        error_behavior,
+        global_options.threads(),
+        global_options.tree_snapshot().as_ref(),
+        &global_options.path_filter()?,
+    )?;
+    eprintln!();
+    file_tree.compute_dir_fingerprints(clones_db, ignore_names);
+    let groups = file_tree.identical_dir_groups(clones_db, ignore_names);
+
+    let current_dir = current_dir().unwrap();
+    let path_print_style = PathPrintStyle::new(global_options, current_dir.as_path());
+
+    for (index, group) in groups.iter().enumerate() {
+        for dir in group.dirs() {
+            print_path(dir, path_print_style, false);
+        }
+        if index < groups.len() - 1 {
+            println!()
+        }
+    }
+
+    if global_options.stats() {
+        eprintln!();
+        bunt::eprintln!(
+            "{[green]:} {$bold}groups,{/$} {[green]:} {$bold}dirs, reclaimable size{/$} {[green]:}",
+            groups.len(),
+            groups.iter().map(|group| group.dirs().len()).sum::<usize>(),
+            Size::from_bytes(
+                groups
+                    .iter()
+                    .map(|group| group.reclaimable_size())
+                    .sum::<u64>()
+            )
+        );
+    }
+
+    Ok(())
+}
+
+fn dirs_command_subsets(
+    dirs: PathRefs,
+    global_options: &CommonOptions,
+    error_behavior: ErrorBehavior,
+    clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+    ignore_names: bool,
+) -> anyhow::Result<()> {
+    let file_tree = progress::tree_with_progress(
+        &dirs,
+        fs,
+        error_behavior,
+        global_options.threads(),
+        global_options.tree_snapshot().as_ref(),
+        &global_options.path_filter()?,
+    )?;
+    eprintln!();
+    let subset_dirs = file_tree.subset_dirs(clones_db, ignore_names);
+
+    let current_dir = current_dir().unwrap();
+    let path_print_style = PathPrintStyle::new(global_options, current_dir.as_path());
+    for subset_dir in &subset_dirs {
+        bunt::println!(
+            "{} {$bold}->{/$} {} {$bold}({[green]:}){/$}",
+            path_print_style.apply(subset_dir.dir()).to_string_lossy(),
+            path_print_style
+                .apply(subset_dir.superset_dir())
+                .to_string_lossy(),
+            subset_dir.reclaimable_size_human()
+        );
+    }
+
+    if global_options.stats() {
+        let dirs = subset_dirs
+            .iter()
+            .unique_by(|subset_dir| subset_dir.dir())
+            .collect_vec();
+        eprintln!();
+        bunt::eprintln!(
+            "{[green]:} {$bold}dirs, reclaimable size{/$} {[green]:}",
+            dirs.len(),
+            Size::from_bytes(
+                dirs.iter()
+                    .map(|subset_dir| subset_dir.reclaimable_size())
+                    .sum::<u64>()
+            )
+        );
+    }
+
+    Ok(())
+}
+
+fn dirs_command(
+    args: &Commands,
+    clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+) -> anyhow::Result<()> {
     let Commands::Dirs {
         dirs,
         map,
         show_refs,
         ref_details,
         unique,
+        identical,
+        ignore_names,
+        subsets,
+        against,
+        single_scope,
+        hide_filtered,
         global_options,
         error_behavior,
         null_line_terminator,
@@ -382,9 +733,35 @@ fn dirs_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
     };
 
     let dirs = dirs.paths();
+    let against = reference_roots(against, clones_db)?;
 
-    if *unique {
-        dirs_command_unique(dirs, global_options, clones_db, *null_line_terminator)?;
+    if *subsets {
+        dirs_command_subsets(
+            dirs,
+            global_options,
+            *error_behavior,
+            clones_db,
+            fs,
+            *ignore_names,
+        )?;
+    } else if *identical {
+        dirs_command_identical(
+            dirs,
+            global_options,
+            *error_behavior,
+            clones_db,
+            fs,
+            *ignore_names,
+        )?;
+    } else if *unique {
+        dirs_command_unique(
+            dirs,
+            global_options,
+            *error_behavior,
+            clones_db,
+            fs,
+            *null_line_terminator,
+        )?;
     } else {
         dirs_command_clones(
             dirs,
@@ -394,6 +771,10 @@ fn dirs_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
             *ref_details,
             *error_behavior,
             clones_db,
+            fs,
+            against.as_deref(),
+            *single_scope,
+            *hide_filtered,
             *null_line_terminator,
         )?;
     }
@@ -401,6 +782,577 @@ fn dirs_command(args: &Commands, clones_db: &ClonesDB) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn print_similarity(
+    similarity: &DirSimilarity,
+    style: PathPrintStyle,
+    file_tree: &fs::Tree,
+    clones_db: &ClonesDB,
+    details: bool,
+    show_dir: bool,
+) -> anyhow::Result<()> {
+    if show_dir {
+        bunt::print!(
+            "{} {$bold}->{/$} ",
+            style.apply(similarity.dir()).to_string_lossy()
+        );
+    }
+    bunt::println!(
+        "{} {$bold}({[green]:.1}% of size, {[green]:.1}% of files contained, {[green]:.1}% similar){/$}",
+        style.apply(similarity.other_dir()).to_string_lossy(),
+        similarity.size_containment() * 100.0,
+        similarity.count_containment() * 100.0,
+        similarity.count_jaccard() * 100.0
+    );
+    if details {
+        let differences = file_tree.dir_differences(similarity, clones_db)?;
+        for file in differences.missing() {
+            print_ref_file(file, style, RefFileType::Missing);
+        }
+        for file in differences.extra() {
+            print_ref_file(file, style, RefFileType::Extra);
+        }
+    }
+    Ok(())
+}
+
+fn similar_command(
+    args: &Commands,
+    clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+) -> anyhow::Result<()> {
+    let Commands::Similar {
+        global_options,
+        top,
+        details,
+        within,
+        error_behavior,
+        dir,
+    } = args
+    else {
+        unreachable!()
+    };
+
+    let current_dir = current_dir().unwrap();
+    let mut paths = if within.is_empty() {
+        vec![current_dir.as_path()]
+    } else {
+        within.iter().map(PathBuf::as_path).collect_vec()
+    };
+    if let Some(dir) = dir {
+        let dir_abs = dir.absolutize()?;
+        let covered = paths.iter().any(|path| {
+            path.absolutize()
+                .is_ok_and(|path| dir_abs.starts_with(path))
+        });
+        if !covered {
+            paths.push(dir);
+        }
+    }
+    let file_tree = progress::tree_with_progress(
+        &PathRefs::new(paths),
+        fs,
+        *error_behavior,
+        global_options.threads(),
+        global_options.tree_snapshot().as_ref(),
+        &global_options.path_filter()?,
+    )?;
+    eprintln!();
+
+    let path_print_style = PathPrintStyle::new(global_options, current_dir.as_path());
+
+    let similarities = match dir {
+        Some(dir) => file_tree.similar_dirs(dir, clones_db)?,
+        None => file_tree.similar_dir_pairs(clones_db),
+    };
+    for similarity in similarities.iter().take(*top) {
+        print_similarity(
+            similarity,
+            path_print_style,
+            &file_tree,
+            clones_db,
+            *details,
+            dir.is_none(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// returns whether all the files are covered
+fn covered_command(
+    args: &Commands,
+    clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+) -> anyhow::Result<bool> {
+    let Commands::Covered {
+        global_options,
+        by,
+        null_line_terminator,
+        error_behavior,
+        dir,
+    } = args
+    else {
+        unreachable!()
+    };
+
+    // the files left out would be reported as covered
+    global_options.check_no_file_selection("covered")?;
+    // the files of the directories which could not be listed would not be checked
+    if *error_behavior != ErrorBehavior::Stop {
+        return Err(anyhow!(
+            "the covered command stops on the listing errors, -E/--error-behavior cannot be {}",
+            error_behavior.to_string().to_lowercase()
+        ));
+    }
+
+    let file_tree = progress::tree_with_progress(
+        &PathRefs::new(vec![dir]),
+        fs,
+        *error_behavior,
+        global_options.threads(),
+        global_options.tree_snapshot().as_ref(),
+        // the ignored files are deleted along with the directory as well
+        &global_options.path_filter()?.without_ignore_files(),
+    )?;
+    eprintln!();
+
+    let roots = reference_roots(by, clones_db)?.unwrap_or_default();
+    let uncovered_files = file_tree.uncovered_files(dir, &roots, clones_db)?;
+
+    let current_dir = current_dir().unwrap();
+    let path_print_style = PathPrintStyle::new(global_options, current_dir.as_path());
+    for file in &uncovered_files {
+        if file.scanned() || *null_line_terminator {
+            print_path(file.path(), path_print_style, *null_line_terminator);
+        } else {
+            bunt::println!(
+                "{} {$yellow}(not scanned){/$}",
+                path_print_style.apply(file.path()).to_string_lossy()
+            );
+        }
+    }
+    io::stdout().flush()?;
+
+    if global_options.stats() {
+        eprintln!();
+        bunt::eprintln!(
+            "{[green]:} {$bold}files not covered, size{/$} {[green]:}",
+            uncovered_files.len(),
+            Size::from_bytes(uncovered_files.iter().map(|file| file.size()).sum::<u64>())
+        );
+    }
+
+    Ok(uncovered_files.is_empty())
+}
+
+fn replication_command(
+    args: &Commands,
+    clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+) -> anyhow::Result<()> {
+    let Commands::Replication {
+        global_options,
+        min_copies,
+        distinct_devices,
+        error_behavior,
+        paths,
+    } = args
+    else {
+        unreachable!()
+    };
+
+    let file_tree = progress::tree_with_progress(
+        &paths.paths(),
+        fs,
+        *error_behavior,
+        global_options.threads(),
+        global_options.tree_snapshot().as_ref(),
+        &global_options.path_filter()?,
+    )?;
+    eprintln!();
+    let report = file_tree.replication_report(clones_db, *min_copies, *distinct_devices, fs);
+
+    let current_dir = current_dir().unwrap();
+    let path_print_style = PathPrintStyle::new(global_options, current_dir.as_path());
+    let copies_kind = if *distinct_devices {
+        "devices"
+    } else {
+        "copies"
+    };
+
+    for content in report.contents() {
+        bunt::println!(
+            "{$bold}{}: {}, {}{/$}",
+            copies_kind,
+            content.copies(),
+            content.size_human()
+        );
+        for file in content.files() {
+            println!("  {}", path_print_style.apply(file).to_string_lossy());
+        }
+    }
+
+    if !report.top_level_dirs().is_empty() {
+        println!();
+        bunt::println!("{$bold}At risk per top-level directory:{/$}");
+        for dir in report.top_level_dirs() {
+            bunt::println!(
+                "  {} {[green]:}",
+                path_print_style.apply(dir.dir()).to_string_lossy(),
+                dir.size_human()
+            );
+        }
+    }
+
+    if global_options.stats() {
+        eprintln!();
+        bunt::eprintln!(
+            "{[green]:} {$bold}contents at risk, size{/$} {[green]:}",
+            report.contents().len(),
+            Size::from_bytes(report.total_size())
+        );
+    }
+
+    Ok(())
+}
+
+fn merge_plan_command(
+    args: &Commands,
+    clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+) -> anyhow::Result<()> {
+    let Commands::MergePlan {
+        global_options,
+        script,
+        error_behavior,
+        source,
+        target,
+    } = args
+    else {
+        unreachable!()
+    };
+
+    // the files left out would not be copied before the source directory is dropped
+    global_options.check_no_file_selection("merge-plan")?;
+
+    let file_tree = progress::tree_with_progress(
+        &PathRefs::new(vec![source, target]),
+        fs,
+        *error_behavior,
+        global_options.threads(),
+        global_options.tree_snapshot().as_ref(),
+        // the ignored files are dropped along with the source directory as well
+        &global_options.path_filter()?.without_ignore_files(),
+    )?;
+    eprintln!();
+    let plan = file_tree.merge_plan(source, target, clones_db)?;
+
+    if *script {
+        plan.write_script(io::stdout().lock())?;
+    } else {
+        let current_dir = current_dir().unwrap();
+        let path_print_style = PathPrintStyle::new(global_options, current_dir.as_path());
+        for action in plan.actions() {
+            match action {
+                MergeAction::Drop { file, copy, .. } => bunt::println!(
+                    "{$green}drop{/$}     {} {$bold}(copy:{/$} {}{$bold}){/$}",
+                    path_print_style.apply(file).to_string_lossy(),
+                    path_print_style.apply(copy).to_string_lossy()
+                ),
+                MergeAction::Copy {
+                    file, destination, ..
+                } => bunt::println!(
+                    "{$yellow}copy{/$}     {} {$bold}->{/$} {}",
+                    path_print_style.apply(file).to_string_lossy(),
+                    path_print_style.apply(destination).to_string_lossy()
+                ),
+                MergeAction::Conflict { file, existing, .. } => bunt::println!(
+                    "{$red}conflict{/$} {} {$bold}<>{/$} {}",
+                    path_print_style.apply(file).to_string_lossy(),
+                    path_print_style.apply(existing).to_string_lossy()
+                ),
+            }
+        }
+    }
+
+    if global_options.stats() {
+        let stats = |kind: fn(&MergeAction) -> bool| {
+            let actions = plan.actions().iter().filter(|action| kind(action));
+            let (count, size) = actions.fold((0, 0), |(count, size), action| {
+                (count + 1, size + action.size())
+            });
+            (count, Size::from_bytes(size))
+        };
+        let drops = stats(|action| matches!(action, MergeAction::Drop { .. }));
+        let copies = stats(|action| matches!(action, MergeAction::Copy { .. }));
+        let conflicts = stats(|action| matches!(action, MergeAction::Conflict { .. }));
+        eprintln!();
+        bunt::eprintln!(
+            "{[green]:} {$bold}files to drop{/$} ({[green]:}), {[green]:} {$bold}files to copy{/$} ({[green]:}), {[green]:} {$bold}conflicts{/$} ({[green]:})",
+            drops.0, drops.1, copies.0, copies.1, conflicts.0, conflicts.1
+        );
+    }
+
+    Ok(())
+}
+
+fn consolidate_command(
+    args: &Commands,
+    clones_db: &ClonesDB,
+    fs: &CatalogsFileSystem,
+) -> anyhow::Result<()> {
+    let Commands::Consolidate {
+        global_options,
+        into,
+        move_files,
+        error_behavior,
+        dir,
+    } = args
+    else {
+        unreachable!()
+    };
+
+    // the files are copied or moved from the directory as it is now, not as it was when the snapshot was saved
+    if global_options.tree_snapshot().is_some() {
+        return Err(anyhow!(
+            "--tree-snapshot cannot be used with the consolidate command"
+        ));
+    }
+    // the files left out would not be transferred before telling that the directory can be deleted
+    global_options.check_no_file_selection("consolidate")?;
+    // the files are transferred on the real file system
+    let (dir_abs, target_abs) = (dir.absolutize()?, into.absolutize()?);
+    if let Some(mountpoint) = fs
+        .offline_mountpoints()
+        .find(|mountpoint| dir_abs.starts_with(mountpoint) || target_abs.starts_with(mountpoint))
+    {
+        return Err(anyhow!(
+            "cannot consolidate with {} as its drive is not attached",
+            mountpoint.to_string_lossy()
+        ));
+    }
+    // the ignored files are deleted along with the directory, they need to be transferred as well
+    let path_filter = global_options.path_filter()?.without_ignore_files();
+
+    let dirs = PathRefs::new(vec![dir]);
+    let file_tree = progress::tree_with_progress(
+        &dirs,
+        &RealFileSystem,
+        *error_behavior,
+        global_options.threads(),
+        None,
+        &path_filter,
+    )?;
+    eprintln!();
+
+    let current_dir = current_dir().unwrap();
+    let path_print_style = PathPrintStyle::new(global_options, current_dir.as_path());
+    let action = if *move_files { "moved" } else { "copied" };
+
+    let target = into.absolutize()?;
+    let transferred_files = consolidate::consolidate(
+        &file_tree,
+        dir,
+        &target,
+        clones_db,
+        *move_files,
+        *error_behavior,
+        |transferred_file| {
+            bunt::println!(
+                "{$green}{}{/$} {} {$bold}->{/$} {}",
+                action,
+                path_print_style
+                    .apply(transferred_file.file())
+                    .to_string_lossy(),
+                path_print_style
+                    .apply(transferred_file.destination())
+                    .to_string_lossy()
+            )
+        },
+    )?;
+
+    if global_options.stats() {
+        eprintln!();
+        bunt::eprintln!(
+            "{[green]:} {$bold}files {}, size{/$} {[green]:}",
+            transferred_files.len(),
+            action,
+            Size::from_bytes(
+                transferred_files
+                    .iter()
+                    .map(|transferred_file| transferred_file.size())
+                    .sum::<u64>()
+            )
+        );
+    }
+
+    // the directory is listed again to check it as it is after the transfers, stopping on the listing errors since
+    // the files of the directories which could not be listed would not be checked
+    let file_tree = progress::tree_with_progress(
+        &dirs,
+        &RealFileSystem,
+        ErrorBehavior::Stop,
+        global_options.threads(),
+        None,
+        &path_filter,
+    )?;
+    eprintln!();
+    let clones_db = consolidate::updated_clones_db(clones_db, &transferred_files);
+    let blocking_files = file_tree.dir_blocking_files(dir, &clones_db)?;
+    if blocking_files.is_empty() {
+        log::info!(
+            "{} is a clone dir, it can be deleted",
+            path_print_style.apply(&dir.absolutize()?).to_string_lossy()
+        );
+    } else {
+        log::warn!(
+            "{} is not a clone dir, {} files have no clone outside of it:",
+            path_print_style.apply(&dir.absolutize()?).to_string_lossy(),
+            blocking_files.len()
+        );
+        for file in blocking_files {
+            print_ref_file(file, path_print_style, RefFileType::Missing);
+        }
+    }
+
+    Ok(())
+}
+
+fn catalog_command(args: &Commands) -> anyhow::Result<()> {
+    let Commands::Catalog {
+        global_options,
+        command,
+    } = args
+    else {
+        unreachable!()
+    };
+
+    let catalogs_dir = global_options.catalogs_dir()?;
+
+    match command {
+        CatalogCommands::Create {
+            mountpoint,
+            name,
+            error_behavior,
+        } => {
+            let mountpoint = mountpoint.absolutize()?;
+            let file_tree = progress::tree_with_progress(
+                &PathRefs::new(vec![&mountpoint]),
+                &RealFileSystem,
+                *error_behavior,
+                global_options.threads(),
+                None,
+                &PathFilter::default(),
+            )?;
+            let mut hashing_progress = CountProgress::new("Hashing files");
+            let catalog = Catalog::create(
+                &mountpoint,
+                name,
+                &file_tree,
+                *error_behavior,
+                global_options.threads(),
+                |processed, total| hashing_progress.update(processed, total),
+            )?;
+            eprintln!();
+            let catalog_path = catalog.save(&catalogs_dir)?;
+            log::info!(
+                "Saved catalog `{}` with {} files: {}",
+                catalog.name(),
+                catalog.file_count(),
+                catalog_path.to_string_lossy()
+            );
+        }
+        CatalogCommands::List => {
+            for catalog in Catalog::list(&catalogs_dir)? {
+                bunt::println!(
+                    "{$bold}{}{/$} {} ({[green]:} files, {[green]:}, created {})",
+                    catalog.name(),
+                    catalog.mountpoint().to_string_lossy(),
+                    catalog.file_count(),
+                    catalog.total_size(),
+                    humantime::format_rfc3339_seconds(catalog.created())
+                );
+            }
+        }
+        CatalogCommands::Check {
+            name,
+            present,
+            null_line_terminator,
+            error_behavior,
+            paths,
+        } => {
+            let catalog = Catalog::load(&catalogs_dir, name)?;
+            let file_tree = progress::tree_with_progress(
+                &paths.paths(),
+                &RealFileSystem,
+                *error_behavior,
+                global_options.threads(),
+                global_options.tree_snapshot().as_ref(),
+                &global_options.path_filter()?,
+            )?;
+            let files = file_tree
+                .traverse_file_nodes(fs::tree::TraversalOrder::Pre)
+                .map(|file| (file.path().to_path_buf(), file.size().unwrap_or_default()))
+                .sorted_unstable()
+                .collect_vec();
+            let mut hashing_progress = CountProgress::new("Hashing files");
+            let locations =
+                catalog.locate_files(&files, global_options.threads(), |processed, total| {
+                    hashing_progress.update(processed, total)
+                });
+            eprintln!();
+
+            let current_dir = current_dir().unwrap();
+            let path_print_style = PathPrintStyle::new(global_options, current_dir.as_path());
+
+            let (mut present_count, mut missing_count, mut missing_size) = (0, 0, 0);
+            for ((file, size), location) in files.iter().zip(locations) {
+                let Some(location) =
+                    error_behavior.handle(location, file, "failed to hash file")?
+                else {
+                    continue;
+                };
+                match location {
+                    Some(location) => {
+                        present_count += 1;
+                        if *present {
+                            let mut file = file.as_path();
+                            if let PathPrintStyle::RelativeTo(prefix) = path_print_style {
+                                file = file.strip_prefix(prefix).unwrap_or(file);
+                            }
+                            bunt::println!(
+                                "{} {$green}=>{/$} {}",
+                                file.to_string_lossy(),
+                                location.to_string_lossy()
+                            );
+                        }
+                    }
+                    None => {
+                        missing_count += 1;
+                        missing_size += size;
+                        if !*present {
+                            print_path(file, path_print_style, *null_line_terminator);
+                        }
+                    }
+                }
+            }
+
+            if global_options.stats() {
+                eprintln!();
+                bunt::eprintln!(
+                    "{[green]:} {$bold}files on{/$} {}{$bold}, missing files{/$} {[green]:}{$bold}, total size{/$} {[green]:}",
+                    present_count,
+                    catalog.name(),
+                    missing_count,
+                    Size::from_bytes(missing_size)
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     ctrlc::set_handler(move || {
         crossterm::execute!(io::stderr(), cursor::Show).unwrap();
@@ -422,11 +1374,96 @@ fn main() -> anyhow::Result<()> {
         .parse_filters(cli.log_level().to_string().as_str())
         .init();
 
-    let clones_db = ClonesDB::read_clones_file(cli.clones_list(), cli.prune())?;
+    if let Commands::Catalog { .. } = &cli.command {
+        return catalog_command(&cli.command);
+    }
+
+    let Some(clones_list) = cli.clones_list() else {
+        return Err(anyhow!(
+            "a clones list file is required, specify it with --clones-list or the CLONES_LIST environment variable"
+        ));
+    };
+    // the catalogs stand in for the drives which are not attached, so that their files are not pruned from the clones
+    // list and can be listed
+    let fs = cli.command.global_options().file_system()?;
+    for mountpoint in fs.offline_mountpoints() {
+        log::info!("Listing {} from its catalog", mountpoint.to_string_lossy());
+    }
+    let mut clones_db = progress::read_clones_file(
+        clones_list,
+        cli.prune(),
+        &fs,
+        cli.command.global_options().threads(),
+    )?;
+
+    let config_path = match cli.config() {
+        Some(config_path) => Some(config_path.clone()),
+        None => config::default_config_path().ok(),
+    };
+    let config = config_path
+        .map(Config::load)
+        .transpose()?
+        .unwrap_or_default();
+    let not_a_backup = PathGlobs::new(config.not_a_backup().iter().chain(cli.not_a_backup()))?;
+    if !not_a_backup.is_empty() {
+        clones_db.mark_not_a_backup(|file| not_a_backup.matches(file));
+    }
+    // the filtered files under the queried paths are left out of the clone groups, unless they are only hidden from
+    // the output, their clones elsewhere still count
+    let queried_paths = cli.command.queried_paths();
+    let path_filter = cli
+        .command
+        .global_options()
+        .path_filter()?
+        .with_roots(queried_paths.iter().copied());
+    let path_filter = match cli.command {
+        Commands::Dirs {
+            hide_filtered: true,
+            ..
+        } => path_filter.ignore_files_only(),
+        // the ignored files are deleted along with the directory, they are checked by covered and planned or
+        // transferred by merge-plan and consolidate as well
+        Commands::Covered { .. } | Commands::MergePlan { .. } | Commands::Consolidate { .. } => {
+            path_filter.without_ignore_files()
+        }
+        _ => path_filter,
+    };
+    let roots = queried_paths
+        .iter()
+        .map(|path| Ok(path.absolutize()?.to_path_buf()))
+        .collect::<io::Result<Vec<_>>>()?;
+    let filtered = |file: &Path, size| {
+        roots.iter().any(|root| file.starts_with(root)) && !path_filter.accepts_clone(file, size)
+    };
+    // the clones list is only rebuilt when some of its files are filtered, which is not the case without any
+    // filter other than the ignore files and without any ignore file in the queried paths
+    let has_filtered_files = !path_filter.is_empty()
+        && clones_db.files_iter().any(|file| {
+            filtered(
+                &file.to_path_buf(),
+                clones_db.group(file.group_id()).file_size(),
+            )
+        });
+    let clones_db = if has_filtered_files {
+        clones_db.without_files(filtered)
+    } else {
+        clones_db
+    };
 
     match &cli.command {
-        cli::Commands::Dirs { .. } => dirs_command(&cli.command, &clones_db),
-        cli::Commands::Files { .. } => files_command(&cli.command, &clones_db),
+        cli::Commands::Dirs { .. } => dirs_command(&cli.command, &clones_db, &fs),
+        cli::Commands::Files { .. } => files_command(&cli.command, &clones_db, &fs),
+        cli::Commands::Similar { .. } => similar_command(&cli.command, &clones_db, &fs),
+        cli::Commands::Replication { .. } => replication_command(&cli.command, &clones_db, &fs),
+        cli::Commands::Covered { .. } => {
+            if !covered_command(&cli.command, &clones_db, &fs)? {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        cli::Commands::MergePlan { .. } => merge_plan_command(&cli.command, &clones_db, &fs),
+        cli::Commands::Consolidate { .. } => consolidate_command(&cli.command, &clones_db, &fs),
+        cli::Commands::Catalog { .. } => unreachable!(),
     }?;
 
     Ok(())
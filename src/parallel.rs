@@ -0,0 +1,82 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+/// number of items a worker thread claims at once
+const BATCH_SIZE: usize = 64;
+
+/// maps `items` with `f` on `threads` worker threads, the results are in the same order as `items`
+/// calls progress on the calling thread with the number of items processed so far
+pub fn map_with_progress<T, R, F>(
+    items: &[T],
+    threads: NonZeroUsize,
+    f: F,
+    mut progress: impl FnMut(usize),
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next_index = AtomicUsize::new(0);
+    let mut results = Vec::with_capacity(items.len());
+    results.resize_with(items.len(), || None);
+    thread::scope(|scope| {
+        let (batch_sender, batch_receiver) = mpsc::channel();
+        for _ in 0..threads.get() {
+            let (next_index, f) = (&next_index, &f);
+            let batch_sender = batch_sender.clone();
+            scope.spawn(move || loop {
+                let start = next_index.fetch_add(BATCH_SIZE, Ordering::Relaxed);
+                if start >= items.len() {
+                    break;
+                }
+                let end = (start + BATCH_SIZE).min(items.len());
+                let batch = items[start..end].iter().map(f).collect::<Vec<_>>();
+                if batch_sender.send((start, batch)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(batch_sender);
+        let mut processed = 0;
+        for (start, batch) in batch_receiver {
+            processed += batch.len();
+            for (result, slot) in batch.into_iter().zip(&mut results[start..]) {
+                *slot = Some(result);
+            }
+            progress(processed);
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.expect("all items should have been processed"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_keep_input_order() {
+        let items = (0..1000).collect::<Vec<u32>>();
+        let mut last_progress = 0;
+        let results = map_with_progress(
+            &items,
+            NonZeroUsize::new(7).unwrap(),
+            |item| item * 2,
+            |processed| last_progress = processed,
+        );
+        assert_eq!(
+            results,
+            items.iter().map(|item| item * 2).collect::<Vec<_>>()
+        );
+        assert_eq!(last_progress, items.len());
+    }
+}
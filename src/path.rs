@@ -7,33 +7,17 @@ use std::{
 
 use anyhow::anyhow;
 use derive_more::{Deref, DerefMut, IntoIterator};
-use getset::{CopyGetters, Getters};
+use getset::CopyGetters;
 use path_absolutize::Absolutize;
 
 use crate::hash::HashValue;
 
-fn parent_and_comps_hashes(path: impl AsRef<Path>) -> (Option<u64>, Vec<u64>) {
-    let comps = path.as_ref().components().fold(vec![], |mut comps, comp| {
-        match comps.last() {
-            None => comps.push(PathBuf::from(comp.as_os_str())),
-            Some(last) => comps.push(last.join(comp.as_os_str())),
-        }
-        comps
-    });
-
-    let mut comp_hashes = comps
-        .iter()
-        .skip(1)
-        .map(HashValue::hash_value)
-        .collect::<Vec<_>>();
-    let parent_hash = if comp_hashes.len() > 1 {
-        Some(comp_hashes[comp_hashes.len() - 2])
-    } else {
-        None
-    };
-    comp_hashes.sort();
-    (parent_hash, comp_hashes)
-}
+pub mod filter;
+pub mod glob;
+pub mod ignore;
+pub mod trie;
+
+pub use trie::PathId;
 
 pub trait HashedPath {
     fn path_hash(&self) -> u64;
@@ -43,28 +27,26 @@ pub trait AsPath {
     fn as_path(&self) -> &Path;
 }
 
-pub trait HashedParentCompsPath {
-    fn comp_hashes(&self) -> &Vec<u64>;
+pub trait InternedPath {
+    fn path_id(&self) -> PathId;
 }
 
-#[derive(Debug, Clone, Eq, Deref, CopyGetters, Getters)]
+/// absolute path interned in the global path trie, only its ID and hash are stored, the path being resolved from the
+/// trie when needed
+#[derive(Debug, Clone, Copy, Eq, CopyGetters)]
 pub struct HashedAbsolutePath {
-    #[deref]
-    path: PathBuf,
     #[getset(get_copy = "pub")]
     hash: u64,
     #[getset(get_copy = "pub")]
-    parent_hash: Option<u64>,
-    #[getset(get = "pub")]
-    comp_hashes: Vec<u64>,
+    id: PathId,
 }
 
 // impl<T: AsRef<Path>> From<T> for HashedAbsolutePath {
 //     fn from(into_path_buf: T) -> Self {
 //         let path = into_path_buf.as_ref().absolutize().unwrap().to_path_buf();
 //         let hash = path.hash_value();
-//         let (parent_hash, comp_hashes) = parent_and_comps_hashes(&path);
-//         Self { path, hash, parent_hash, comp_hashes }
+//         let id = PathId::intern(&path);
+//         Self { path, hash, id }
 //     }
 // }
 
@@ -72,13 +54,9 @@ impl From<PathBuf> for HashedAbsolutePath {
     fn from(path_buf: PathBuf) -> Self {
         let path = path_buf.absolutize().unwrap().to_path_buf();
         let hash = path.hash_value();
-        let (parent_hash, comp_hashes) = parent_and_comps_hashes(&path);
-        Self {
-            path,
-            hash,
-            parent_hash,
-            comp_hashes,
-        }
+        // the absolutized paths are normalized
+        let id = PathId::intern(&path).unwrap();
+        Self { hash, id }
     }
 }
 
@@ -101,8 +79,9 @@ impl From<&Path> for HashedAbsolutePath {
 }
 
 impl HashedAbsolutePath {
-    pub fn to_absolute_path_ref(&self) -> HashedAbsolutePathRef {
-        HashedAbsolutePathRef::from(self)
+    /// resolves the path from the global path trie
+    pub fn to_path_buf(&self) -> PathBuf {
+        self.id.to_path_buf()
     }
 
     pub fn starts_with(&self, path: impl AsRef<Path>) -> anyhow::Result<bool> {
@@ -110,22 +89,20 @@ impl HashedAbsolutePath {
         if !path.is_absolute() {
             return Err(anyhow!("path is not absolute: {}", path.to_string_lossy()));
         }
-        if path.as_os_str() == "/" {
-            return Ok(true);
-        }
-        Ok(self.comp_hash_matches(path.hash_value()))
+        // every ancestor of an interned path is interned so a path which is not cannot be a prefix
+        Ok(PathId::get(path).is_some_and(|path_id| self.id.starts_with(path_id)))
     }
 
     pub fn starts_with_hap(&self, path: impl AsRef<HashedAbsolutePath>) -> bool {
-        let path = path.as_ref();
-        if path.as_os_str() == "/" {
-            return true;
-        }
-        self.comp_hash_matches(path.hash)
+        self.id.starts_with(path.as_ref().id)
     }
 
-    pub fn comp_hash_matches(&self, comp_hash: u64) -> bool {
-        self.comp_hashes().binary_search(&comp_hash).is_ok()
+    pub fn starts_with_id(&self, path_id: PathId) -> bool {
+        self.id.starts_with(path_id)
+    }
+
+    pub fn parent_id(&self) -> Option<PathId> {
+        self.id.parent()
     }
 
     pub fn parent_is(&self, path: impl AsRef<Path>) -> anyhow::Result<bool> {
@@ -133,17 +110,16 @@ impl HashedAbsolutePath {
         if !path.is_absolute() {
             return Err(anyhow!("path is not absolute: {}", path.to_string_lossy()));
         }
-        match &self.parent_hash {
-            Some(parent_hash) => Ok(*parent_hash == path.hash_value()),
-            None => Ok(path.as_os_str() == "/"),
-        }
+        Ok(PathId::get(path).is_some_and(|path_id| self.parent_id() == Some(path_id)))
     }
 
     pub fn parent_is_hap(&self, path: impl AsRef<Self>) -> bool {
-        match &self.parent_hash {
-            Some(parent_hash) => *parent_hash == path.as_ref().hash(),
-            None => path.as_ref().as_os_str() == "/",
-        }
+        self.parent_id() == Some(path.as_ref().id)
+    }
+
+    #[cfg(test)]
+    pub fn with_forced_hash(self, hash: u64) -> Self {
+        Self { hash, ..self }
     }
 }
 
@@ -153,12 +129,6 @@ impl AsRef<HashedAbsolutePath> for HashedAbsolutePath {
     }
 }
 
-impl AsRef<Path> for HashedAbsolutePath {
-    fn as_ref(&self) -> &Path {
-        self.path.as_path()
-    }
-}
-
 impl HashedPath for HashedAbsolutePath {
     fn path_hash(&self) -> u64 {
         self.hash
@@ -171,52 +141,45 @@ impl Hash for HashedAbsolutePath {
     }
 }
 
-impl AsPath for HashedAbsolutePath {
-    fn as_path(&self) -> &Path {
-        self.path.as_path()
-    }
-}
-
-impl HashedParentCompsPath for HashedAbsolutePath {
-    fn comp_hashes(&self) -> &Vec<u64> {
-        &self.comp_hashes
+impl InternedPath for HashedAbsolutePath {
+    fn path_id(&self) -> PathId {
+        self.id
     }
 }
 
+// paths are compared through their interned IDs which, unlike the hashes, cannot collide
 impl PartialEq for HashedAbsolutePath {
     fn eq(&self, other: &Self) -> bool {
-        self.hash == other.hash
+        self.id == other.id
     }
 }
 
 impl PartialOrd for HashedAbsolutePath {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.path.cmp(&other.path))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for HashedAbsolutePath {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.path.cmp(&other.path)
+        self.id.cmp_paths(other.id)
     }
 }
 
 impl Display for HashedAbsolutePath {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.path.to_string_lossy())
+        f.write_str(&self.to_path_buf().to_string_lossy())
     }
 }
 
-#[derive(Debug, Clone, Eq, Deref, CopyGetters, Getters)]
+#[derive(Debug, Clone, Copy, Eq, Deref, CopyGetters)]
 pub struct HashedAbsolutePathRef<'a> {
     #[deref]
     path: &'a Path,
     #[getset(get_copy = "pub")]
     hash: u64,
     #[getset(get_copy = "pub")]
-    parent_hash: Option<u64>,
-    #[getset(get = "pub")]
-    comp_hashes: Vec<u64>,
+    id: PathId,
 }
 
 impl<'a> HashedAbsolutePathRef<'a> {
@@ -225,13 +188,8 @@ impl<'a> HashedAbsolutePathRef<'a> {
             return Err(anyhow!("path is not absolute: {}", path.to_string_lossy()));
         }
         let hash = path.hash_value();
-        let (parent_hash, comp_hashes) = parent_and_comps_hashes(path);
-        Ok(Self {
-            path,
-            hash,
-            parent_hash,
-            comp_hashes,
-        })
+        let id = PathId::intern(path)?;
+        Ok(Self { path, hash, id })
     }
 
     pub fn inner(&self) -> &'a Path {
@@ -243,22 +201,19 @@ impl<'a> HashedAbsolutePathRef<'a> {
         if !path.is_absolute() {
             return Err(anyhow!("path is not absolute: {}", path.to_string_lossy()));
         }
-        if path.as_os_str() == "/" {
-            return Ok(true);
-        }
-        Ok(self.comp_hash_matches(path.hash_value()))
+        Ok(PathId::get(path).is_some_and(|path_id| self.id.starts_with(path_id)))
     }
 
     pub fn starts_with_hashed_path(&self, path: impl AsRef<HashedAbsolutePath>) -> bool {
-        let path = path.as_ref();
-        if path.as_os_str() == "/" {
-            return true;
-        }
-        self.comp_hash_matches(path.hash)
+        self.id.starts_with(path.as_ref().id)
+    }
+
+    pub fn starts_with_id(&self, path_id: PathId) -> bool {
+        self.id.starts_with(path_id)
     }
 
-    pub fn comp_hash_matches(&self, comp_hash: u64) -> bool {
-        self.comp_hashes().binary_search(&comp_hash).is_ok()
+    pub fn parent_id(&self) -> Option<PathId> {
+        self.id.parent()
     }
 
     pub fn parent_is(&self, path: impl AsRef<Path>) -> anyhow::Result<bool> {
@@ -266,17 +221,16 @@ impl<'a> HashedAbsolutePathRef<'a> {
         if !path.is_absolute() {
             return Err(anyhow!("path is not absolute: {}", path.to_string_lossy()));
         }
-        match &self.parent_hash {
-            Some(parent_hash) => Ok(*parent_hash == path.hash_value()),
-            None => Ok(false),
-        }
+        Ok(PathId::get(path).is_some_and(|path_id| self.parent_id() == Some(path_id)))
     }
 
     pub fn parent_is_hap(&self, path: impl AsRef<HashedAbsolutePath>) -> bool {
-        match &self.parent_hash {
-            Some(parent_hash) => *parent_hash == path.as_ref().hash(),
-            None => path.as_ref().as_os_str() == "/",
-        }
+        self.parent_id() == Some(path.as_ref().id)
+    }
+
+    #[cfg(test)]
+    pub fn with_forced_hash(self, hash: u64) -> Self {
+        Self { hash, ..self }
     }
 }
 
@@ -300,24 +254,8 @@ impl<'a> TryFrom<&'a Path> for HashedAbsolutePathRef<'a> {
             return Err(anyhow!("path is not absolute: {}", path.to_string_lossy()));
         }
         let hash = path.hash_value();
-        let (parent_hash, comp_hashes) = parent_and_comps_hashes(path);
-        Ok(Self {
-            path,
-            hash,
-            parent_hash,
-            comp_hashes,
-        })
-    }
-}
-
-impl<'a> From<&'a HashedAbsolutePath> for HashedAbsolutePathRef<'a> {
-    fn from(hashed_abs_path: &'a HashedAbsolutePath) -> Self {
-        HashedAbsolutePathRef {
-            path: &hashed_abs_path.path,
-            hash: hashed_abs_path.hash,
-            parent_hash: hashed_abs_path.parent_hash,
-            comp_hashes: hashed_abs_path.comp_hashes.clone(),
-        }
+        let id = PathId::intern(path)?;
+        Ok(Self { path, hash, id })
     }
 }
 
@@ -339,15 +277,15 @@ impl AsPath for HashedAbsolutePathRef<'_> {
     }
 }
 
-impl HashedParentCompsPath for HashedAbsolutePathRef<'_> {
-    fn comp_hashes(&self) -> &Vec<u64> {
-        &self.comp_hashes
+impl InternedPath for HashedAbsolutePathRef<'_> {
+    fn path_id(&self) -> PathId {
+        self.id
     }
 }
 
 impl PartialEq for HashedAbsolutePathRef<'_> {
     fn eq(&self, other: &Self) -> bool {
-        self.hash == other.hash
+        self.id == other.id
     }
 }
 
@@ -460,9 +398,9 @@ mod tests {
             let x = HashedAbsolutePath::from("/a/b/c");
             let y = HashedAbsolutePath::from("/a");
             let z = HashedAbsolutePath::from("/b");
-            assert!(x.starts_with_hap(&y));
-            assert!(x.starts_with(y.as_path()).unwrap());
-            assert!(!x.starts_with(z.as_path()).unwrap());
+            assert!(x.starts_with_hap(y));
+            assert!(x.starts_with(y.to_path_buf()).unwrap());
+            assert!(!x.starts_with(z.to_path_buf()).unwrap());
         }
 
         #[test]
@@ -470,10 +408,10 @@ mod tests {
             let x = HashedAbsolutePath::from("/a/b/c");
             let y = HashedAbsolutePath::from("/a/b");
             let z = HashedAbsolutePath::from("/b");
-            assert!(x.parent_is_hap(&y));
-            assert!(x.parent_is(y.as_path()).unwrap());
-            assert!(!x.parent_is_hap(&z));
-            assert!(!x.parent_is(z.as_path()).unwrap());
+            assert!(x.parent_is_hap(y));
+            assert!(x.parent_is(y.to_path_buf()).unwrap());
+            assert!(!x.parent_is_hap(z));
+            assert!(!x.parent_is(z.to_path_buf()).unwrap());
         }
 
         #[test]
@@ -493,6 +431,112 @@ mod tests {
             let y = HashedAbsolutePath::from("/a/b/def");
             assert!(x < y);
             assert!(y > x);
+            // the paths are compared component-wise, an ancestor coming first
+            let parent = HashedAbsolutePath::from("/a/b");
+            let sibling = HashedAbsolutePath::from("/a/bc");
+            assert!(parent < x);
+            assert!(x < sibling);
+            assert_eq!(x.cmp(&x), std::cmp::Ordering::Equal);
+        }
+
+        #[test]
+        fn starts_with_is_component_wise() {
+            let x = HashedAbsolutePath::from("/a/bc/d");
+            let y = HashedAbsolutePath::from("/a/b");
+            let root = HashedAbsolutePath::from("/");
+            assert!(!x.starts_with_hap(y));
+            assert!(x.starts_with_hap(x));
+            assert!(x.starts_with_hap(root));
+            assert!(!x.starts_with(y.to_path_buf()).unwrap());
+        }
+    }
+
+    mod hash_collisions {
+        use std::{collections::HashSet, path::Path};
+
+        use crate::path::{HashedAbsolutePath, HashedAbsolutePathRef};
+
+        #[test]
+        fn eq() {
+            let x = HashedAbsolutePath::from("/a/b/c").with_forced_hash(42);
+            let y = HashedAbsolutePath::from("/d/e").with_forced_hash(42);
+            assert_ne!(x, y);
+            let x_ref = HashedAbsolutePathRef::new(Path::new("/a/b/c"))
+                .unwrap()
+                .with_forced_hash(42);
+            let y_ref = HashedAbsolutePathRef::new(Path::new("/d/e"))
+                .unwrap()
+                .with_forced_hash(42);
+            assert_ne!(x_ref, y_ref);
+            let z = HashedAbsolutePath::from("/a/b/c");
+            assert_eq!(x, z);
+        }
+
+        #[test]
+        fn set_membership() {
+            let x = HashedAbsolutePath::from("/a/b/c").with_forced_hash(42);
+            let y = HashedAbsolutePath::from("/d/e").with_forced_hash(42);
+            let set = HashSet::from([x, y]);
+            assert_eq!(set.len(), 2);
+            let z = HashedAbsolutePath::from("/d/f").with_forced_hash(42);
+            assert!(!set.contains(&z));
+        }
+
+        #[test]
+        fn ancestry() {
+            let dir = HashedAbsolutePath::from("/a/b").with_forced_hash(42);
+            let file = HashedAbsolutePath::from("/c/d/e");
+            let colliding_parent = HashedAbsolutePath::from("/c/d").with_forced_hash(42);
+            let file_ref = HashedAbsolutePathRef::new(Path::new("/c/d/e")).unwrap();
+            assert!(!file.starts_with_hap(dir));
+            assert!(!file.parent_is_hap(dir));
+            assert!(!file_ref.starts_with_hashed_path(dir));
+            assert!(!file_ref.parent_is_hap(dir));
+            assert!(file_ref.starts_with_hashed_path(colliding_parent));
+            assert!(file_ref.parent_is_hap(colliding_parent));
+        }
+    }
+
+    mod path_trie {
+        use std::path::Path;
+
+        use crate::path::{trie::PathTrie, HashedAbsolutePathRef, PathId};
+
+        #[test]
+        fn intern() {
+            let mut trie = PathTrie::new();
+            let x = trie.intern("/a/b/c").unwrap();
+            let y = trie.intern("/a/b/d").unwrap();
+            assert_eq!(trie.intern("/a/b/c").unwrap(), x);
+            assert_ne!(x, y);
+            assert_eq!(trie.parent(x), trie.parent(y));
+            assert_eq!(trie.get("/a/b"), trie.parent(x));
+            assert_eq!(trie.get("/a/b/e"), None);
+            assert_eq!(trie.get("/"), Some(PathId::ROOT));
+            assert_eq!(trie.path(x), Path::new("/a/b/c"));
+            assert_eq!(trie.depth(x), 3);
+        }
+
+        #[test]
+        fn starts_with() {
+            let mut trie = PathTrie::new();
+            let x = trie.intern("/a/b/c").unwrap();
+            let y = trie.intern("/a").unwrap();
+            let z = trie.intern("/b").unwrap();
+            assert!(trie.starts_with(x, y));
+            assert!(trie.starts_with(x, x));
+            assert!(trie.starts_with(x, PathId::ROOT));
+            assert!(!trie.starts_with(x, z));
+            assert!(!trie.starts_with(y, x));
+        }
+
+        #[test]
+        fn not_normalized() {
+            let mut trie = PathTrie::new();
+            assert!(trie.intern("a/b").is_err());
+            assert!(trie.intern("/a/../b").is_err());
+            assert_eq!(trie.get("/a/../b"), None);
+            assert!(HashedAbsolutePathRef::new(Path::new("/a/..")).is_err());
         }
     }
 }
@@ -0,0 +1,200 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use path_absolutize::Absolutize;
+use regex::Regex;
+
+use super::{glob::PathGlobs, ignore::IgnoreFiles};
+use crate::fs::{
+    attribute_filter::AttributeFilter, tree::metadata::NodeMetadata, walk_bounds::WalkBounds,
+};
+
+/// include and exclude patterns selecting the paths to analyse, matched against absolute paths,
+/// along with the rules of the ignore files, the size, modification time and type criteria of the files and the bounds of the walks
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: PathGlobs,
+    exclude: PathGlobs,
+    regexes: Vec<Regex>,
+    ignore_files: Option<Arc<IgnoreFiles>>,
+    /// absolute walked or queried directories, the `.lscignore` files are only read inside of them
+    roots: Vec<PathBuf>,
+    attributes: AttributeFilter,
+    bounds: WalkBounds,
+    follow_symlinks: bool,
+}
+
+impl PathFilter {
+    pub fn new(
+        include: impl IntoIterator<Item = impl AsRef<str>>,
+        exclude: impl IntoIterator<Item = impl AsRef<str>>,
+        regexes: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            include: PathGlobs::new(include)?,
+            exclude: PathGlobs::new(exclude)?,
+            regexes: regexes
+                .into_iter()
+                .map(|regex| Regex::new(regex.as_ref()))
+                .collect::<Result<_, _>>()?,
+            ignore_files: None,
+            roots: vec![],
+            attributes: AttributeFilter::default(),
+            bounds: WalkBounds::default(),
+            follow_symlinks: false,
+        })
+    }
+
+    /// also leaves out the paths ignored by `ignore_files`
+    pub fn with_ignore_files(mut self, ignore_files: IgnoreFiles) -> Self {
+        self.ignore_files = Some(Arc::new(ignore_files));
+        self
+    }
+
+    /// also leaves out the files whose attributes are not accepted by `attributes`
+    pub fn with_attributes(mut self, attributes: AttributeFilter) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// also leaves out the walked paths out of `bounds`
+    pub fn with_bounds(mut self, bounds: WalkBounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    /// returns the filter with its walk bounds relative to the walked or queried directories `roots`, the `.lscignore`
+    /// files being read inside of them only
+    pub fn with_roots<'a>(&self, roots: impl IntoIterator<Item = &'a Path>) -> Self {
+        let roots = roots.into_iter().collect::<Vec<_>>();
+        Self {
+            bounds: self.bounds.with_roots(roots.iter().copied()),
+            roots: roots
+                .into_iter()
+                .filter_map(|root| Some(root.absolutize().ok()?.to_path_buf()))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn bounds(&self) -> &WalkBounds {
+        &self.bounds
+    }
+
+    /// makes the walks follow the symbolic links
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    pub fn follows_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    /// returns the filter without its patterns and attribute criteria, only leaving out the ignored paths and the paths
+    /// out of the walk bounds, the symbolic links being followed the same way
+    pub fn ignore_files_only(&self) -> Self {
+        Self {
+            ignore_files: self.ignore_files.clone(),
+            roots: self.roots.clone(),
+            bounds: self.bounds.clone(),
+            follow_symlinks: self.follow_symlinks,
+            ..Self::default()
+        }
+    }
+
+    /// returns the filter without the rules of the ignore files
+    pub fn without_ignore_files(&self) -> Self {
+        Self {
+            ignore_files: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty()
+            && self.exclude.is_empty()
+            && self.regexes.is_empty()
+            && self.ignore_files.is_none()
+            && self.attributes.is_empty()
+            && self.bounds.is_unbounded()
+            && !self.follow_symlinks
+    }
+
+    /// see [AttributeFilter::has_time_criteria]
+    pub fn has_time_criteria(&self) -> bool {
+        self.attributes.has_time_criteria()
+    }
+
+    /// the `.lscignore` files are read from the outermost root containing `path` down to it
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.ignore_files.as_ref().is_some_and(|ignore_files| {
+            let root = self
+                .roots
+                .iter()
+                .filter(|root| path.starts_with(root))
+                .min_by_key(|root| root.components().count());
+            ignore_files.is_ignored(path, is_dir, root.map(PathBuf::as_path))
+        })
+    }
+
+    /// returns true if the directory is neither excluded nor ignored, the include patterns and regular expressions
+    /// only apply to files
+    pub fn accepts_dir(&self, dir: impl AsRef<Path>) -> bool {
+        let dir = dir.as_ref();
+        !self.exclude.matches(dir) && !self.is_ignored(dir, true)
+    }
+
+    /// returns true if the file is neither excluded nor ignored and matches one of the include patterns, one of the regular expressions
+    /// and one of the file types when there are some, its size and modification time are not checked
+    pub fn accepts_file(&self, file: impl AsRef<Path>) -> bool {
+        let file = file.as_ref();
+        self.attributes.accepts_type(file)
+            && !self.exclude.matches(file)
+            && !self.is_ignored(file, false)
+            && (self.include.is_empty() || self.include.matches(file))
+            && (self.regexes.is_empty() || {
+                let file = file.to_string_lossy();
+                self.regexes.iter().any(|regex| regex.is_match(&file))
+            })
+    }
+
+    /// returns true if the walked directory is accepted and within the walk bounds
+    pub fn accepts_dir_metadata(&self, dir: impl AsRef<Path>, metadata: &NodeMetadata) -> bool {
+        let dir = dir.as_ref();
+        self.accepts_dir(dir) && self.bounds.accepts_dir(dir, metadata)
+    }
+
+    /// returns true if the walked file is accepted along with the size and modification time recorded while walking it
+    /// and is within the walk bounds
+    pub fn accepts_file_metadata(&self, file: impl AsRef<Path>, metadata: &NodeMetadata) -> bool {
+        let file = file.as_ref();
+        self.accepts_file(file)
+            && self.attributes.accepts_metadata(metadata)
+            && self.bounds.accepts_file(file)
+    }
+
+    /// returns true if the file of the clones list is accepted along with its size, the modification time not being known
+    pub fn accepts_clone(&self, file: impl AsRef<Path>, size: u64) -> bool {
+        self.accepts_file(file) && self.attributes.accepts_size(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_files() {
+        let filter = PathFilter::new(["*.jpg", "/photos"], ["**/cache"], [r"/20\d\d/"]).unwrap();
+        assert!(filter.accepts_file("/home/2023/a.jpg"));
+        assert!(filter.accepts_file("/photos/2024/raw.cr2"));
+        assert!(!filter.accepts_file("/photos/cache/2024/a.jpg"));
+        assert!(!filter.accepts_file("/home/a.jpg"));
+        assert!(filter.accepts_dir("/home"));
+        assert!(!filter.accepts_dir("/photos/cache"));
+        assert!(PathFilter::default().is_empty());
+    }
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// set of glob patterns matched against absolute paths
+#[derive(Debug, Clone, Default)]
+pub struct PathGlobs {
+    globs: GlobSet,
+}
+
+impl PathGlobs {
+    pub fn new(
+        patterns: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern.as_ref())?);
+        }
+        Ok(Self {
+            globs: builder.build()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    /// returns true if `path` or one of its ancestors matches one of the patterns
+    pub fn matches(&self, path: impl AsRef<Path>) -> bool {
+        path.as_ref()
+            .ancestors()
+            .any(|ancestor| self.globs.is_match(ancestor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_path_or_ancestor() {
+        let globs = PathGlobs::new(["/tmp", "**/.Trash*"]).unwrap();
+        assert!(globs.matches("/tmp/a/b"));
+        assert!(globs.matches("/home/user/.Trash-1000/files/photo.jpg"));
+        assert!(!globs.matches("/home/user/tmp/photo.jpg"));
+        assert!(!PathGlobs::default().matches("/tmp"));
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+
+/// name of the ignore files read in the directories
+pub const IGNORE_FILE_NAME: &str = ".lscignore";
+
+/// rules of the ignore files, in gitignore syntax: a global one and the `.lscignore` files of the directories
+/// which apply to their content, the deepest rules take precedence
+#[derive(Debug)]
+pub struct IgnoreFiles {
+    global: Gitignore,
+    /// rules of the directories read so far, None for the directories without ignore file
+    dirs: RwLock<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl IgnoreFiles {
+    /// reads the global ignore file if there is one, the `.lscignore` files are read when needed
+    pub fn new(global_file: Option<&Path>) -> anyhow::Result<Self> {
+        let global = match global_file.filter(|file| file.is_file()) {
+            Some(file) => {
+                let mut builder = GitignoreBuilder::new("/");
+                if let Some(error) = builder.add(file) {
+                    return Err(error.into());
+                }
+                builder.build()?
+            }
+            None => Gitignore::empty(),
+        };
+        Ok(Self {
+            global,
+            dirs: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// reads the `.lscignore` file of `dir` if there is one
+    fn read_dir_rules(dir: &Path) -> Option<Arc<Gitignore>> {
+        let file = dir.join(IGNORE_FILE_NAME);
+        if !file.is_file() {
+            return None;
+        }
+        let mut builder = GitignoreBuilder::new(dir);
+        if let Some(error) = builder.add(&file) {
+            log::warn!("invalid ignore file {}: {error}", file.to_string_lossy());
+        }
+        match builder.build() {
+            Ok(gitignore) => Some(Arc::new(gitignore)),
+            Err(error) => {
+                log::warn!("invalid ignore file {}: {error}", file.to_string_lossy());
+                None
+            }
+        }
+    }
+
+    fn dir_rules(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        if let Some(rules) = self.dirs.read().unwrap().get(dir) {
+            return rules.clone();
+        }
+        // read without holding the lock, the walker threads reaching the same directory at the same time read it twice
+        let rules = Self::read_dir_rules(dir);
+        self.dirs
+            .write()
+            .unwrap()
+            .insert(dir.to_path_buf(), rules.clone());
+        rules
+    }
+
+    /// returns true if the absolute `path` or one of its parents is ignored, the ignore files themselves are ignored
+    /// the `.lscignore` files are read in the directories from `root` down to `path`, only the global rules apply
+    /// without `root`
+    pub fn is_ignored(&self, path: &Path, is_dir: bool, root: Option<&Path>) -> bool {
+        if !is_dir && path.file_name() == Some(OsStr::new(IGNORE_FILE_NAME)) {
+            return true;
+        }
+        let dirs = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| root.is_some_and(|root| dir.starts_with(root)));
+        for dir in dirs {
+            let Some(rules) = self.dir_rules(dir) else {
+                continue;
+            };
+            match rules.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        self.global
+            .matched_path_or_any_parents(path, is_dir)
+            .is_ignore()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignored_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs_err::create_dir_all(root.join("photos")).unwrap();
+        let global_file = root.join("global");
+        std::fs::write(&global_file, ".DS_Store\n").unwrap();
+        std::fs::write(root.join(IGNORE_FILE_NAME), "*.swp\n").unwrap();
+        std::fs::write(root.join("photos").join(IGNORE_FILE_NAME), "!keep.swp\n").unwrap();
+        let ignore_files = IgnoreFiles::new(Some(&global_file)).unwrap();
+        let is_ignored = |path: &str| ignore_files.is_ignored(&root.join(path), false, Some(root));
+        assert!(is_ignored("photos/.DS_Store"));
+        assert!(is_ignored("photos/a.swp"));
+        assert!(!is_ignored("photos/keep.swp"));
+        assert!(is_ignored(IGNORE_FILE_NAME));
+        assert!(!is_ignored("photos/a.jpg"));
+        // the ignore files above the root do not apply
+        let photos = root.join("photos");
+        assert!(!ignore_files.is_ignored(&photos.join("a.swp"), false, Some(&photos)));
+        assert!(ignore_files.is_ignored(&photos.join(".DS_Store"), false, None));
+    }
+}
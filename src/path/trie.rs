@@ -0,0 +1,238 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    path::{self, Path, PathBuf},
+    sync::RwLock,
+};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// the interned paths are never freed, the trie growing with every distinct path until the process exits
+    /// the lookups share the read lock, the write lock is only taken by `PathId::intern` for the paths which are not
+    /// interned yet, once a read locked lookup failed
+    /// the walks intern the paths of the tree nodes from the thread merging the listings, the walker threads only
+    /// list the directories, so that they do not contend on the write lock
+    static ref PATH_TRIE: RwLock<PathTrie> = RwLock::new(PathTrie::new());
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("path is not absolute or not normalized: {0}")]
+pub struct NotNormalizedPath(PathBuf);
+
+/// compact identifier of an absolute path interned in the global path trie
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PathId(u32);
+
+impl PathId {
+    /// ID of the `/` path
+    pub const ROOT: PathId = PathId(0);
+
+    /// interns `path` in the global path trie and returns its ID
+    /// fails if `path` is not absolute or not normalized
+    pub fn intern(path: impl AsRef<Path>) -> Result<Self, NotNormalizedPath> {
+        let path = path.as_ref();
+        if let Some(path_id) = Self::get(path) {
+            return Ok(path_id);
+        }
+        PATH_TRIE.write().unwrap().intern(path)
+    }
+
+    /// returns the ID of `path` if it has been interned
+    pub fn get(path: impl AsRef<Path>) -> Option<Self> {
+        PATH_TRIE.read().unwrap().get(path)
+    }
+
+    pub fn parent(self) -> Option<Self> {
+        PATH_TRIE.read().unwrap().parent(self)
+    }
+
+    /// number of components after the root, the root having a depth of 0
+    pub fn depth(self) -> u32 {
+        PATH_TRIE.read().unwrap().depth(self)
+    }
+
+    /// returns true if `base` is this path or one of its ancestors
+    pub fn starts_with(self, base: PathId) -> bool {
+        PATH_TRIE.read().unwrap().starts_with(self, base)
+    }
+
+    /// returns true if `ancestor` is a strict ancestor of this path
+    pub fn is_descendant_of(self, ancestor: PathId) -> bool {
+        self != ancestor && self.starts_with(ancestor)
+    }
+
+    pub fn file_name(self) -> Option<OsString> {
+        PATH_TRIE
+            .read()
+            .unwrap()
+            .file_name(self)
+            .map(OsStr::to_os_string)
+    }
+
+    pub fn to_path_buf(self) -> PathBuf {
+        PATH_TRIE.read().unwrap().path(self)
+    }
+
+    /// returns the IDs of the path and all its ancestors, from the root to the path
+    pub fn ancestors(self) -> Vec<PathId> {
+        PATH_TRIE.read().unwrap().ancestors(self)
+    }
+
+    /// compares the paths component-wise, the same way as `Path::cmp`
+    pub fn cmp_paths(self, other: PathId) -> Ordering {
+        PATH_TRIE.read().unwrap().cmp_paths(self, other)
+    }
+}
+
+#[derive(Debug)]
+struct TrieNode {
+    name: Box<OsStr>,
+    parent: Option<PathId>,
+    depth: u32,
+}
+
+/// trie of path components where every path is stored once as a name and a pointer to its parent
+#[derive(Debug)]
+pub struct PathTrie {
+    nodes: Vec<TrieNode>,
+    children: HashMap<(PathId, Box<OsStr>), PathId>,
+}
+
+impl Default for PathTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PathTrie {
+    pub fn new() -> Self {
+        let root = TrieNode {
+            name: OsStr::new("/").into(),
+            parent: None,
+            depth: 0,
+        };
+        Self {
+            nodes: vec![root],
+            children: HashMap::new(),
+        }
+    }
+
+    fn node(&self, path_id: PathId) -> &TrieNode {
+        &self.nodes[path_id.0 as usize]
+    }
+
+    /// returns the names of the components of `path` after the root
+    /// fails if `path` is not absolute or contains `.` or `..` components
+    fn normal_components(path: &Path) -> Result<impl Iterator<Item = &OsStr>, NotNormalizedPath> {
+        let is_normalized = path.components().all(|component| {
+            matches!(
+                component,
+                path::Component::RootDir | path::Component::Normal(_)
+            )
+        });
+        if !path.is_absolute() || !is_normalized {
+            return Err(NotNormalizedPath(path.to_path_buf()));
+        }
+        Ok(path.components().filter_map(|component| match component {
+            path::Component::Normal(name) => Some(name),
+            _ => None,
+        }))
+    }
+
+    /// fails if `path` is not absolute or not normalized
+    pub fn intern(&mut self, path: impl AsRef<Path>) -> Result<PathId, NotNormalizedPath> {
+        let path = path.as_ref();
+        let mut current = PathId::ROOT;
+        for name in Self::normal_components(path)? {
+            let key = (current, Box::from(name));
+            current = match self.children.get(&key) {
+                Some(child) => *child,
+                None => {
+                    let child = PathId(
+                        u32::try_from(self.nodes.len()).expect("too many paths in path trie"),
+                    );
+                    self.nodes.push(TrieNode {
+                        name: key.1.clone(),
+                        parent: Some(current),
+                        depth: self.node(current).depth + 1,
+                    });
+                    self.children.insert(key, child);
+                    child
+                }
+            };
+        }
+        Ok(current)
+    }
+
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<PathId> {
+        let mut current = PathId::ROOT;
+        for name in Self::normal_components(path.as_ref()).ok()? {
+            current = *self.children.get(&(current, Box::from(name)))?;
+        }
+        Some(current)
+    }
+
+    pub fn parent(&self, path_id: PathId) -> Option<PathId> {
+        self.node(path_id).parent
+    }
+
+    pub fn depth(&self, path_id: PathId) -> u32 {
+        self.node(path_id).depth
+    }
+
+    pub fn file_name(&self, path_id: PathId) -> Option<&OsStr> {
+        let node = self.node(path_id);
+        node.parent.map(|_| node.name.as_ref())
+    }
+
+    pub fn starts_with(&self, path_id: PathId, base: PathId) -> bool {
+        let base_depth = self.node(base).depth;
+        let mut current = path_id;
+        let mut current_depth = self.node(current).depth;
+        while current_depth > base_depth {
+            current = self.node(current).parent.unwrap();
+            current_depth -= 1;
+        }
+        current == base
+    }
+
+    pub fn ancestors(&self, path_id: PathId) -> Vec<PathId> {
+        let mut ancestors = Vec::with_capacity(self.node(path_id).depth as usize + 1);
+        let mut current = Some(path_id);
+        while let Some(path_id) = current {
+            ancestors.push(path_id);
+            current = self.node(path_id).parent;
+        }
+        ancestors.reverse();
+        ancestors
+    }
+
+    pub fn cmp_paths(&self, path_id: PathId, other: PathId) -> Ordering {
+        if path_id == other {
+            return Ordering::Equal;
+        }
+        let (ancestors, other_ancestors) = (self.ancestors(path_id), self.ancestors(other));
+        // the first differing components decide, otherwise the ancestor comes first
+        match ancestors
+            .iter()
+            .zip(&other_ancestors)
+            .find(|(ancestor, other_ancestor)| ancestor != other_ancestor)
+        {
+            Some((&ancestor, &other_ancestor)) => self
+                .node(ancestor)
+                .name
+                .cmp(&self.node(other_ancestor).name),
+            None => ancestors.len().cmp(&other_ancestors.len()),
+        }
+    }
+
+    pub fn path(&self, path_id: PathId) -> PathBuf {
+        let mut path = PathBuf::from("/");
+        for ancestor in self.ancestors(path_id).into_iter().skip(1) {
+            path.push(&*self.node(ancestor).name);
+        }
+        path
+    }
+}
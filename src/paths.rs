@@ -1,23 +1,27 @@
 use std::{
     collections::HashSet,
-    io::{self, Write},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
 };
 
-use crossterm::cursor;
 use derive_more::{Deref, IntoIterator};
 use getset::CopyGetters;
 use itertools::Itertools;
-use num_format::{Locale, ToFormattedString};
-use scopeguard::defer;
 use size::Size;
 
 use crate::{
-    call_rate_limiter::CallRateLimiter,
-    clones::db::{ClonesDB, DirCloneFilesStats, PartitionedDirClones},
+    clones::db::{
+        CloneGroupFileCountAndSize, CloneGroupReclaimable, ClonesDB, DirCloneFilesStats,
+        PartitionedDirClones,
+    },
     error_behavior::ErrorBehavior,
-    fs,
-    path::HashedAbsolutePathRef,
+    fs::{
+        self,
+        tree::snapshot::{SnapshotOptions, TreeSnapshot},
+        vfs::{FileSystem, FilteredFileSystem},
+        walk_bounds::WalkBounds,
+    },
+    path::{filter::PathFilter, HashedAbsolutePath, HashedAbsolutePathRef},
 };
 
 #[derive(Debug, Clone, Deref, Default)]
@@ -226,7 +230,15 @@ impl<'a> AsRef<PathRefs<'a>> for PathRefs<'a> {
 }
 
 pub trait TreeWithProgress<'a> {
-    fn tree_with_progress(&'a self, error_behavior: ErrorBehavior) -> anyhow::Result<fs::Tree>;
+    fn tree_with_progress(
+        &'a self,
+        fs: &impl FileSystem,
+        error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
+        snapshot_options: Option<&SnapshotOptions>,
+        filter: &PathFilter,
+        progress: impl FnMut(u64, u64),
+    ) -> anyhow::Result<fs::Tree>;
 }
 
 impl<'a, T> TreeWithProgress<'a> for T
@@ -234,30 +246,96 @@ where
     T: 'a,
     &'a T: IntoIterator<Item = &'a Path>,
 {
-    fn tree_with_progress(&'a self, error_behavior: ErrorBehavior) -> anyhow::Result<fs::Tree> {
-        let mut tree = fs::Tree::default();
-        let (mut total_dir_count, mut total_file_count) = (0, 0);
-        defer!(crossterm::execute!(io::stderr(), cursor::Show).unwrap());
-        crossterm::execute!(io::stderr(), cursor::Hide).unwrap();
-        let progress_func = |(dirs, files): (u64, u64)| {
-            bunt::eprint!(
-                "\r{$green}INFO{/$}  {$bold}>{/$} Listing files: {} dirs - {} files",
-                dirs.to_formatted_string(&Locale::en),
-                files.to_formatted_string(&Locale::en)
+    /// with `snapshot_options` the tree is built from the snapshot file when it covers all the paths, otherwise
+    /// the paths are walked and their listings are saved in the snapshot file
+    /// only the entries accepted by `filter` are added to the tree, the paths are listed from `fs`
+    /// calls progress with the number of directories and files found so far in all the paths
+    fn tree_with_progress(
+        &'a self,
+        fs: &impl FileSystem,
+        error_behavior: ErrorBehavior,
+        threads: NonZeroUsize,
+        snapshot_options: Option<&SnapshotOptions>,
+        filter: &PathFilter,
+        mut progress: impl FnMut(u64, u64),
+    ) -> anyhow::Result<fs::Tree> {
+        let paths = self.into_iter().collect_vec();
+        // the snapshot records the listings without following the symbolic links
+        let snapshot_options = match snapshot_options {
+            Some(snapshot_options) if filter.follows_symlinks() => {
+                log::warn!(
+                    "Not using tree snapshot {} as the symbolic links are followed",
+                    snapshot_options.path().to_string_lossy()
+                );
+                None
+            }
+            snapshot_options => snapshot_options,
+        };
+        let snapshot = match snapshot_options {
+            Some(snapshot_options) => TreeSnapshot::load(snapshot_options.path())?
+                .filter(|snapshot| paths.iter().all(|path| snapshot.covers(path))),
+            None => None,
+        };
+        if let (Some(snapshot_options), Some(_)) = (snapshot_options, &snapshot) {
+            log::info!(
+                "Using tree snapshot: {}",
+                snapshot_options.path().to_string_lossy()
             );
-            io::stderr().flush().unwrap();
+        }
+        let revalidate = snapshot_options.is_some_and(SnapshotOptions::revalidate);
+
+        // the walk bounds are relative to the walked paths
+        let filter = &filter.with_roots(paths.iter().copied());
+        // the snapshot records the unfiltered listings, the tree is filtered once the snapshot has been saved
+        let no_filter = PathFilter::default();
+        let walk_filter = if snapshot_options.is_some() {
+            &no_filter
+        } else {
+            filter
         };
-        let mut progress_display = CallRateLimiter::new(0.1, progress_func);
-        for path in self.into_iter() {
-            let (dir_count, file_count) =
-                tree.extend_with_progress(path, error_behavior, |dir_count, file_count| {
-                    progress_display.call((dir_count, file_count))
-                })?;
+
+        let mut tree = fs::Tree::default();
+        let (mut total_dir_count, mut total_file_count) = (0, 0);
+        for path in &paths {
+            let progress = |dir_count, file_count| {
+                progress(total_dir_count + dir_count, total_file_count + file_count)
+            };
+            let (dir_count, file_count) = match &snapshot {
+                Some(snapshot) => tree.extend_from_snapshot_with_progress(
+                    path,
+                    fs,
+                    snapshot,
+                    revalidate,
+                    error_behavior,
+                    threads,
+                    progress,
+                )?,
+                None => tree.extend_from_fs_with_progress(
+                    path,
+                    &FilteredFileSystem::new(fs, walk_filter),
+                    error_behavior,
+                    threads,
+                    progress,
+                )?,
+            };
             total_dir_count += dir_count;
             total_file_count += file_count;
         }
-        progress_display.call_unconditional((total_dir_count, total_file_count));
-        eprintln!();
+        progress(total_dir_count, total_file_count);
+
+        if let Some(snapshot_options) = snapshot_options {
+            // a snapshot used without revalidation is already up to date
+            if snapshot.is_none() || revalidate {
+                let mut snapshot = TreeSnapshot::load(snapshot_options.path())?.unwrap_or_default();
+                snapshot.update(&tree, &paths);
+                snapshot.save(snapshot_options.path())?;
+            }
+        }
+
+        if walk_filter.is_empty() && !filter.is_empty() {
+            tree = tree.filtered(filter);
+        }
+
         Ok(tree)
     }
 }
@@ -295,22 +373,33 @@ impl CloneStats {
     }
 }
 
+/// the paths are looked up in `fs` to tell the directories from the files
 pub trait Clones<'a> {
     fn clones(
         &'a self,
         recursive: bool,
+        bounds: &WalkBounds,
         clones_db: &'a ClonesDB,
-    ) -> (DirCloneFilesStats, PathRefs<'a>);
+        fs: &impl FileSystem,
+    ) -> (DirCloneFilesStats, Paths);
     fn inside_clones(
         &'a self,
         recursive: bool,
+        single_scope: bool,
+        bounds: &WalkBounds,
         clones_db: &'a ClonesDB,
-    ) -> (CloneStats, PathRefs<'a>);
+        fs: &impl FileSystem,
+    ) -> (CloneStats, Paths);
+    /// with `single_scope` all the directories together form the inside set instead of each directory separately
+    /// the files out of `bounds` are outside clones
     fn clone_groups(
         &'a self,
         recursive: bool,
+        single_scope: bool,
+        bounds: &WalkBounds,
         clones_db: &'a ClonesDB,
-    ) -> Vec<PartitionedDirClones<'a>>;
+        fs: &impl FileSystem,
+    ) -> Vec<PartitionedDirClones>;
 }
 
 impl<'a, T> Clones<'a> for T
@@ -321,20 +410,29 @@ where
     fn clones(
         &'a self,
         recursive: bool,
+        bounds: &WalkBounds,
         clones_db: &'a ClonesDB,
-    ) -> (DirCloneFilesStats, PathRefs<'a>) {
+        fs: &impl FileSystem,
+    ) -> (DirCloneFilesStats, Paths) {
         let mut stats = DirCloneFilesStats::default();
         let clones = self
             .into_iter()
             .flat_map(|path| {
-                if path.is_dir() {
-                    let clones = clones_db.dir_clone_files(path, recursive);
+                if fs.is_dir(path) {
+                    let clones = clones_db.dir_clone_files(path, recursive, bounds);
                     stats += *clones.stats();
-                    clones.into_iter().map(|c| c.inner()).collect()
-                } else if path.is_file() && clones_db.file_is_a_clone(path) {
-                    path.into()
+                    clones
+                        .into_iter()
+                        .map(|file| clones_db.file_path(file).to_path_buf())
+                        .collect_vec()
+                } else if fs.is_file(path)
+                    && clones_db
+                        .file_id(path)
+                        .is_some_and(|file_id| clones_db.file_has_backup(file_id))
+                {
+                    vec![path.to_path_buf()]
                 } else {
-                    PathRefs::default()
+                    vec![]
                 }
             })
             .unique()
@@ -345,11 +443,14 @@ where
     fn inside_clones(
         &'a self,
         recursive: bool,
+        single_scope: bool,
+        bounds: &WalkBounds,
         clones_db: &'a ClonesDB,
-    ) -> (CloneStats, PathRefs<'a>) {
+        fs: &impl FileSystem,
+    ) -> (CloneStats, Paths) {
         let mut stats = CloneStats::default();
         let clones = self
-            .clone_groups(recursive, clones_db)
+            .clone_groups(recursive, single_scope, bounds, clones_db, fs)
             .iter()
             .filter(|&group| (group.inside().len() > 1))
             .flat_map(|group| {
@@ -358,7 +459,10 @@ where
                 stats.total_size += inside.total_size();
                 stats.reclaimable_count += inside.reclaimable_count();
                 stats.reclaimable_size += inside.reclaimable_size();
-                inside.iter().map(|path| path.inner()).collect_vec()
+                inside
+                    .iter()
+                    .map(|&file| clones_db.file_path(file).to_path_buf())
+                    .collect_vec()
             })
             .collect();
         (stats, clones)
@@ -367,11 +471,17 @@ where
     fn clone_groups(
         &'a self,
         recursive: bool,
+        single_scope: bool,
+        bounds: &WalkBounds,
         clones_db: &'a ClonesDB,
-    ) -> Vec<PartitionedDirClones<'a>> {
-        self.into_iter()
-            .filter(|path| path.is_dir())
-            .flat_map(|dir| clones_db.dir_clone_groups(dir, recursive))
+        fs: &impl FileSystem,
+    ) -> Vec<PartitionedDirClones> {
+        let dirs = self.into_iter().filter(|path| fs.is_dir(path));
+        if single_scope {
+            let dirs = dirs.map(HashedAbsolutePath::from).collect_vec();
+            return clones_db.dirs_clone_groups_hap(&dirs, recursive, bounds);
+        }
+        dirs.flat_map(|dir| clones_db.dir_clone_groups(dir, recursive, bounds))
             .collect()
     }
 }
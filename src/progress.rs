@@ -0,0 +1,121 @@
+use std::{
+    io::{self, Write},
+    num::NonZeroUsize,
+};
+
+use crossterm::cursor;
+use lsclones::{
+    clones::db::{ClonesDB, PruneProgress},
+    error_behavior::ErrorBehavior,
+    fs::{self, tree::snapshot::SnapshotOptions, vfs::FileSystem},
+    path::filter::PathFilter,
+    paths::{PathRefs, TreeWithProgress},
+};
+use num_format::{Locale, ToFormattedString};
+use scopeguard::defer;
+
+use crate::call_rate_limiter::CallRateLimiter;
+
+/// builds the file tree of `paths` listed from `fs` displaying the number of directories and files listed so far
+pub fn tree_with_progress(
+    paths: &PathRefs,
+    fs: &impl FileSystem,
+    error_behavior: ErrorBehavior,
+    threads: NonZeroUsize,
+    snapshot_options: Option<&SnapshotOptions>,
+    filter: &PathFilter,
+) -> anyhow::Result<fs::Tree> {
+    defer!(crossterm::execute!(io::stderr(), cursor::Show).unwrap());
+    crossterm::execute!(io::stderr(), cursor::Hide).unwrap();
+    let progress_func = |(dirs, files): (u64, u64)| {
+        bunt::eprint!(
+            "\r{$green}INFO{/$}  {$bold}>{/$} Listing files: {} dirs - {} files",
+            dirs.to_formatted_string(&Locale::en),
+            files.to_formatted_string(&Locale::en)
+        );
+        io::stderr().flush().unwrap();
+    };
+    let mut progress_display = CallRateLimiter::new(0.1, progress_func);
+    let mut counts = (0, 0);
+    let tree = paths.tree_with_progress(
+        fs,
+        error_behavior,
+        threads,
+        snapshot_options,
+        filter,
+        |dir_count, file_count| {
+            counts = (dir_count, file_count);
+            progress_display.call(counts);
+        },
+    )?;
+    progress_display.call_unconditional(counts);
+    eprintln!();
+    Ok(tree)
+}
+
+type CountProgressFunc<'a> = Box<dyn FnMut((usize, usize)) + 'a>;
+
+/// displays a message followed by the number of items processed and the total number of items
+pub struct CountProgress<'a> {
+    display: CallRateLimiter<(usize, usize), CountProgressFunc<'a>>,
+}
+
+impl<'a> CountProgress<'a> {
+    pub fn new(message: &'a str) -> Self {
+        let progress_func = move |(index, total): (usize, usize)| {
+            let percent = (index * 100).checked_div(total).unwrap_or(100);
+            bunt::eprint!(
+                "\r{$green}INFO{/$}  {$bold}>{/$} {} {} / {} ({}%)",
+                message,
+                index.to_formatted_string(&Locale::en),
+                total.to_formatted_string(&Locale::en),
+                percent
+            );
+            io::stderr().flush().unwrap();
+        };
+        Self {
+            display: CallRateLimiter::new(0.1, Box::new(progress_func)),
+        }
+    }
+
+    /// the progress is always displayed once all the items have been processed
+    pub fn update(&mut self, processed: usize, total: usize) {
+        if processed == total {
+            self.display.call_unconditional((processed, total));
+        } else {
+            self.display.call((processed, total));
+        }
+    }
+}
+
+/// reads the clones list file displaying the pruning progress
+pub fn read_clones_file(
+    path: impl AsRef<std::path::Path>,
+    prune: bool,
+    fs: &impl FileSystem,
+    threads: NonZeroUsize,
+) -> anyhow::Result<ClonesDB> {
+    log::info!(
+        "Loading clones list file: {}",
+        path.as_ref().to_string_lossy()
+    );
+    if prune {
+        defer!(crossterm::execute!(io::stderr(), cursor::Show).unwrap());
+        crossterm::execute!(io::stderr(), cursor::Hide).unwrap();
+        let mut scanned_paths_progress = CountProgress::new("Pruning scanned dirs list");
+        let mut files_progress = CountProgress::new("Pruning clone files list");
+        let clones_db =
+            ClonesDB::read_clones_file(path, prune, fs, threads, |progress| match progress {
+                PruneProgress::ScannedPaths { processed, total } => {
+                    scanned_paths_progress.update(processed, total)
+                }
+                PruneProgress::Files { processed, total } => {
+                    files_progress.update(processed, total)
+                }
+            })?;
+        eprintln!();
+        Ok(clones_db)
+    } else {
+        ClonesDB::read_clones_file(path, prune, fs, threads, |_| {})
+    }
+}